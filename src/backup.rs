@@ -0,0 +1,574 @@
+//! Backups of the installed binaries, taken before each update, that allow to roll back to the
+//! previous version if the new one turns out to be broken.
+//!
+//! Each backup lives in `$CARGO_HOME/bup/backups/<crate>/<timestamp>-<version>-<root hash>`, and
+//! contains a copy of the binaries as well as the package's entries in cargo's listings.
+
+use std::{
+    collections::BTreeSet,
+    fs::{self, File, OpenOptions},
+    io::{ErrorKind, Read, Seek, Write},
+    iter,
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+    thread,
+    time::{Duration, Instant, SystemTime},
+};
+
+use anstream::println;
+use anyhow::{bail, Context, Result};
+use fs4::fs_std::FileExt;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+use crate::{
+    cargo::{InstallInfo, PackageId},
+    colors, common,
+};
+
+/// Snapshot of a single package, before it was updated.
+pub struct Backup {
+    dir: PathBuf,
+    meta: Meta,
+}
+
+#[derive(Deserialize, Serialize)]
+struct Meta {
+    name: String,
+    version: String,
+    /// Installation root that the binaries were copied from.
+    root: PathBuf,
+    /// Point in time the backup was taken, in seconds since the Unix epoch.
+    created: u64,
+    #[serde(flatten)]
+    entries: Entries,
+    /// Binaries that were copied into the backup.
+    bins: BTreeSet<String>,
+}
+
+impl Backup {
+    /// Copy the package's binaries and listing entries into a new backup, inside the given
+    /// backups directory.
+    ///
+    /// Older backups are left alone until the update succeeded, see [`Self::prune`]. Otherwise,
+    /// the new backup is meant to be discarded again.
+    pub fn create(backups: &Path, package: &PackageId, info: &InstallInfo) -> Result<Self> {
+        let created = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .map_or(0, |d| d.as_secs());
+        let dir = backups.join(&package.name).join(format!(
+            "{created}-{}-{}",
+            package.version,
+            common::root_hash(&info.root)
+        ));
+        fs::create_dir_all(dir.join("bin"))
+            .with_context(|| format!("failed creating backup directory `{}`", dir.display()))?;
+
+        let listings = Listings::lock(&info.root)?;
+        let entries = listings.entries(&package.name)?;
+
+        let mut bins = BTreeSet::new();
+        for bin in &info.bins {
+            match fs::copy(info.root.join("bin").join(bin), dir.join("bin").join(bin)) {
+                Ok(_) => {
+                    bins.insert(bin.clone());
+                }
+                Err(e) if e.kind() == ErrorKind::NotFound => {}
+                Err(e) => return Err(e).with_context(|| format!("failed backing up `{bin}`")),
+            }
+        }
+
+        drop(listings);
+
+        let meta = Meta {
+            name: package.name.clone(),
+            version: package.version.to_string(),
+            root: info.root.clone(),
+            created,
+            entries,
+            bins,
+        };
+        fs::write(dir.join("backup.json"), serde_json::to_vec_pretty(&meta)?)?;
+
+        Ok(Self { dir, meta })
+    }
+
+    /// List all backups of the package, from newest to oldest.
+    pub fn list(backups: &Path, name: &str) -> Result<Vec<Self>> {
+        let parent = backups.join(name);
+
+        let entries = match fs::read_dir(&parent) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e.into()),
+        };
+
+        let mut backups = Vec::new();
+        for entry in entries {
+            let dir = entry?.path();
+            let Ok(meta) = fs::read(dir.join("backup.json")) else {
+                continue;
+            };
+            let meta = serde_json::from_slice::<Meta>(&meta)
+                .with_context(|| format!("failed parsing backup in `{}`", dir.display()))?;
+
+            backups.push(Self { dir, meta });
+        }
+
+        backups.sort_by(|a, b| {
+            b.meta
+                .created
+                .cmp(&a.meta.created)
+                .then_with(|| b.dir.cmp(&a.dir))
+        });
+
+        Ok(backups)
+    }
+
+    /// Remove the oldest backups of the package in the given installation root, keeping at most
+    /// `keep` of them.
+    pub fn prune(backups: &Path, name: &str, root: &Path, keep: usize) -> Result<()> {
+        let backups = Self::list(backups, name)?;
+        let own = backups
+            .into_iter()
+            .filter(|backup| backup.meta.root == root);
+
+        for old in own.skip(keep) {
+            fs::remove_dir_all(&old.dir)
+                .with_context(|| format!("failed removing backup `{}`", old.dir.display()))?;
+        }
+
+        Ok(())
+    }
+
+    pub fn version(&self) -> &str {
+        &self.meta.version
+    }
+
+    /// Remove the backup, as the backed up version is still installed.
+    pub fn discard(self) -> Result<()> {
+        fs::remove_dir_all(&self.dir)
+            .with_context(|| format!("failed removing backup `{}`", self.dir.display()))?;
+
+        // Don't leave an empty directory behind, if it was the package's first backup.
+        if let Some(parent) = self.dir.parent() {
+            fs::remove_dir(parent).ok();
+        }
+
+        Ok(())
+    }
+
+    /// Put the backed up binaries and listing entries back in place, replacing whatever version of
+    /// the package is currently installed. The backup is removed afterwards.
+    pub fn restore(self) -> Result<()> {
+        let bin_dir = self.meta.root.join("bin");
+        let mut listings = Listings::lock(&self.meta.root)?;
+
+        let current = listings.replace(&self.meta.name, &self.meta.entries)?;
+
+        // Binaries that only the newer version provided.
+        for bin in current.difference(&self.meta.bins) {
+            fs::remove_file(bin_dir.join(bin)).ok();
+        }
+
+        for bin in &self.meta.bins {
+            // Copy next to the target first, as a running binary can't be overwritten.
+            let temp = bin_dir.join(format!(".{bin}.bup"));
+            fs::copy(self.dir.join("bin").join(bin), &temp)
+                .with_context(|| format!("failed restoring `{bin}`"))?;
+            fs::rename(&temp, bin_dir.join(bin))
+                .with_context(|| format!("failed restoring `{bin}`"))?;
+        }
+
+        listings.write()?;
+        drop(listings);
+
+        fs::remove_dir_all(&self.dir).ok();
+
+        Ok(())
+    }
+}
+
+/// Run each of the package's binaries with the given arguments, to check that they at least start
+/// up properly. The binaries are taken from cargo's listings, as the update might have added or
+/// removed some.
+pub fn smoke_test(root: &Path, name: &str, args: &[String]) -> Result<()> {
+    const TIMEOUT: Duration = Duration::from_secs(30);
+
+    let entries = Listings::lock(root)?.entries(name)?;
+    let bins = entries
+        .v2
+        .as_ref()
+        .and_then(|entry| entry.get("bins"))
+        .map(|bins| serde_json::from_value::<BTreeSet<String>>(bins.clone()))
+        .transpose()
+        .context("failed parsing `.crates2.json`")?
+        .or(entries.v1)
+        .unwrap_or_default();
+
+    for bin in &bins {
+        let cmdline = iter::once(bin)
+            .chain(args)
+            .cloned()
+            .collect::<Vec<_>>()
+            .join(" ");
+
+        let path = root.join("bin").join(bin);
+        let mut child = Command::new(&path)
+            .args(args)
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+            .with_context(|| format!("failed running `{cmdline}`"))?;
+
+        let start = Instant::now();
+        let status = loop {
+            if let Some(status) = child.try_wait()? {
+                break status;
+            }
+
+            if start.elapsed() > TIMEOUT {
+                child.kill().ok();
+                child.wait().ok();
+                bail!("`{cmdline}` didn't finish within {}s", TIMEOUT.as_secs());
+            }
+
+            thread::sleep(Duration::from_millis(50));
+        };
+
+        if !status.success() {
+            bail!("`{cmdline}` failed with {status}");
+        }
+    }
+
+    Ok(())
+}
+
+/// Restore the latest backup of the package.
+pub fn rollback(name: &str) -> Result<()> {
+    let Some(backup) = Backup::list(&dir()?, name)?.into_iter().next() else {
+        bail!("no backups of `{name}` found");
+    };
+
+    let version = backup.meta.version.clone();
+    backup.restore()?;
+
+    println!(
+        "rolled back {} to {}",
+        colors::green(name).bold(),
+        colors::blue(version).bold()
+    );
+
+    Ok(())
+}
+
+/// Location of the backups of all crates.
+pub fn dir() -> Result<PathBuf> {
+    Ok(home::cargo_home()?.join("bup/backups"))
+}
+
+/// Entries of a single package in cargo's listings.
+#[derive(Debug, Default, Deserialize, PartialEq, Serialize)]
+struct Entries {
+    /// The package's identifier in cargo's listings.
+    key: Option<String>,
+    /// The package's entry in `.crates2.json`.
+    v2: Option<Value>,
+    /// The package's binaries, as listed in `.crates.toml`.
+    v1: Option<BTreeSet<String>>,
+}
+
+/// Both of cargo's listings in an installation root, locked for exclusive access the same way
+/// cargo does, so concurrent installations don't interfere.
+struct Listings {
+    v1: LockedFile,
+    v2: LockedFile,
+}
+
+struct LockedFile {
+    /// The open file, unless it doesn't exist.
+    file: Option<File>,
+    content: String,
+}
+
+impl LockedFile {
+    fn open(path: &Path) -> Result<Self> {
+        let mut file = match OpenOptions::new().read(true).write(true).open(path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == ErrorKind::NotFound => {
+                return Ok(Self {
+                    file: None,
+                    content: String::new(),
+                })
+            }
+            Err(e) => {
+                return Err(e).with_context(|| format!("failed opening `{}`", path.display()))
+            }
+        };
+        file.lock_exclusive()
+            .with_context(|| format!("failed locking `{}`", path.display()))?;
+
+        let mut content = String::new();
+        file.read_to_string(&mut content)?;
+
+        Ok(Self {
+            file: Some(file),
+            content,
+        })
+    }
+
+    fn write(&mut self) -> Result<()> {
+        if let Some(file) = &mut self.file {
+            file.rewind()?;
+            file.set_len(0)?;
+            file.write_all(self.content.as_bytes())?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Listings {
+    fn lock(root: &Path) -> Result<Self> {
+        Ok(Self {
+            v1: LockedFile::open(&root.join(".crates.toml"))?,
+            v2: LockedFile::open(&root.join(".crates2.json"))?,
+        })
+    }
+
+    /// Find the entries of the package in both listings.
+    fn entries(&self, name: &str) -> Result<Entries> {
+        let v2 = self.parse_v2()?;
+        let v1 = self.parse_v1()?;
+
+        let v2_entry = v2
+            .get("installs")
+            .and_then(Value::as_object)
+            .and_then(|installs| installs.iter().find(|(key, _)| key_name(key) == name));
+        let v1_entry = v1
+            .get("v1")
+            .and_then(toml::Value::as_table)
+            .and_then(|v1| v1.iter().find(|(key, _)| key_name(key) == name));
+
+        let key = v2_entry
+            .map(|(key, _)| key.clone())
+            .or_else(|| v1_entry.map(|(key, _)| key.clone()));
+        let v1_bins = v1_entry
+            .map(|(_, bins)| bins.clone().try_into())
+            .transpose()?;
+
+        Ok(Entries {
+            key,
+            v2: v2_entry.map(|(_, value)| value.clone()),
+            v1: v1_bins,
+        })
+    }
+
+    /// Replace the package's entries with the given ones, returning the binaries of the replaced
+    /// entries.
+    fn replace(&mut self, name: &str, entries: &Entries) -> Result<BTreeSet<String>> {
+        let mut removed = BTreeSet::new();
+
+        let mut v2 = self.parse_v2()?;
+        if let Some(Value::Object(installs)) = v2.get_mut("installs") {
+            for (_, entry) in take_entries(installs, name) {
+                if let Some(bins) = entry.get("bins").and_then(Value::as_array) {
+                    removed.extend(bins.iter().filter_map(Value::as_str).map(ToOwned::to_owned));
+                }
+            }
+
+            if let (Some(key), Some(entry)) = (&entries.key, &entries.v2) {
+                installs.insert(key.to_owned(), entry.clone());
+            }
+
+            self.v2.content = serde_json::to_string(&v2)?;
+        }
+
+        let mut v1 = self.parse_v1()?;
+        if let Some(toml::Value::Table(table)) = v1.get_mut("v1") {
+            let keys = table
+                .keys()
+                .filter(|key| key_name(key) == name)
+                .cloned()
+                .collect::<Vec<_>>();
+
+            for key in keys {
+                if let Some(bins) = table.remove(&key).and_then(|bins| bins.try_into().ok()) {
+                    removed.extend::<BTreeSet<String>>(bins);
+                }
+            }
+
+            if let (Some(key), Some(bins)) = (&entries.key, &entries.v1) {
+                table.insert(key.to_owned(), toml::Value::try_from(bins)?);
+            }
+
+            self.v1.content = toml::to_string(&v1)?;
+        }
+
+        Ok(removed)
+    }
+
+    fn write(&mut self) -> Result<()> {
+        self.v1.write()?;
+        self.v2.write()
+    }
+
+    fn parse_v2(&self) -> Result<Map<String, Value>> {
+        if self.v2.content.trim().is_empty() {
+            return Ok(Map::new());
+        }
+
+        serde_json::from_str(&self.v2.content).context("failed parsing `.crates2.json`")
+    }
+
+    fn parse_v1(&self) -> Result<toml::Table> {
+        toml::from_str(&self.v1.content).context("failed parsing `.crates.toml`")
+    }
+}
+
+/// Remove all entries of the package from the listing.
+fn take_entries(installs: &mut Map<String, Value>, name: &str) -> Vec<(String, Value)> {
+    let keys = installs
+        .keys()
+        .filter(|key| key_name(key) == name)
+        .cloned()
+        .collect::<Vec<_>>();
+
+    keys.into_iter()
+        .filter_map(|key| installs.remove(&key).map(|value| (key, value)))
+        .collect()
+}
+
+/// Extract the package name from a serialized package identifier like
+/// `foo 1.0.0 (registry+https://github.com/rust-lang/crates.io-index)`.
+fn key_name(key: &str) -> &str {
+    key.split_once(' ').map_or(key, |(name, _)| name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn replace_entries() {
+        let dir = tempfile::tempdir().unwrap();
+        let dir = dir.path();
+        fs::write(
+            dir.join(".crates.toml"),
+            "[v1]\n\"foo 1.1.0 (registry+https://x)\" = [\"foo\", \"foo-extra\"]\n\"bar 0.1.0 \
+             (registry+https://x)\" = [\"bar\"]\n",
+        )
+        .unwrap();
+        fs::write(
+            dir.join(".crates2.json"),
+            r#"{"installs":{"foo 1.1.0 (registry+https://x)":{"bins":["foo","foo-extra"]},"bar 0.1.0 (registry+https://x)":{"bins":["bar"]}}}"#,
+        )
+        .unwrap();
+
+        let mut listings = Listings::lock(dir).unwrap();
+        let entries = Entries {
+            key: Some("foo 1.0.0 (registry+https://x)".to_owned()),
+            v2: Some(serde_json::json!({ "bins": ["foo"] })),
+            v1: Some(BTreeSet::from(["foo".to_owned()])),
+        };
+        let removed = listings.replace("foo", &entries).unwrap();
+        listings.write().unwrap();
+        drop(listings);
+
+        assert_eq!(
+            BTreeSet::from(["foo".to_owned(), "foo-extra".to_owned()]),
+            removed
+        );
+
+        let listings = Listings::lock(dir).unwrap();
+        assert_eq!(entries, listings.entries("foo").unwrap());
+        assert!(listings.entries("bar").unwrap().key.is_some());
+    }
+
+    #[test]
+    fn create_and_restore() {
+        let dir = tempfile::tempdir().unwrap();
+        let (root, backups) = (dir.path().join("root"), dir.path().join("backups"));
+        fs::create_dir_all(root.join("bin")).unwrap();
+
+        let install = |version: &str, bins: &[&str]| {
+            for bin in bins {
+                fs::write(root.join("bin").join(bin), version).unwrap();
+            }
+            fs::write(
+                root.join(".crates.toml"),
+                format!("[v1]\n\"foo {version} (registry+https://x)\" = {bins:?}\n"),
+            )
+            .unwrap();
+            fs::write(
+                root.join(".crates2.json"),
+                serde_json::json!({
+                    "installs": { format!("foo {version} (registry+https://x)"): { "bins": bins } }
+                })
+                .to_string(),
+            )
+            .unwrap();
+
+            let package = serde_json::from_value::<PackageId>(
+                format!("foo {version} (registry+https://x)").into(),
+            )
+            .unwrap();
+            let info = InstallInfo {
+                bins: bins.iter().map(|bin| (*bin).to_owned()).collect(),
+                root: root.clone(),
+                ..InstallInfo::default()
+            };
+
+            (package, info)
+        };
+        let versions = || {
+            Backup::list(&backups, "foo")
+                .unwrap()
+                .iter()
+                .map(|backup| backup.version().to_owned())
+                .collect::<Vec<_>>()
+        };
+
+        for version in ["1.0.0", "1.1.0", "1.2.0"] {
+            let (package, info) = install(version, &["foo"]);
+            Backup::create(&backups, &package, &info).unwrap();
+        }
+        assert_eq!(vec!["1.2.0", "1.1.0", "1.0.0"], versions());
+
+        Backup::prune(&backups, "foo", &root, 2).unwrap();
+        assert_eq!(vec!["1.2.0", "1.1.0"], versions());
+
+        // The newer version brings an additional binary, that is gone after restoring.
+        install("1.3.0", &["foo", "foo-extra"]);
+        let backup = Backup::list(&backups, "foo").unwrap().remove(0);
+        backup.restore().unwrap();
+
+        assert_eq!("1.2.0", fs::read_to_string(root.join("bin/foo")).unwrap());
+        assert!(!root.join("bin/foo-extra").exists());
+
+        let listings = Listings::lock(&root).unwrap();
+        let entries = listings.entries("foo").unwrap();
+        assert_eq!(
+            Some("foo 1.2.0 (registry+https://x)"),
+            entries.key.as_deref()
+        );
+        assert_eq!(Some(BTreeSet::from(["foo".to_owned()])), entries.v1);
+        drop(listings);
+
+        assert_eq!(vec!["1.1.0"], versions());
+
+        let (package, info) = install("1.2.0", &["foo"]);
+        Backup::create(&backups, &package, &info)
+            .unwrap()
+            .discard()
+            .unwrap();
+        assert_eq!(vec!["1.1.0"], versions());
+
+        // Backups of the same crate in other roots are left alone.
+        Backup::prune(&backups, "foo", &dir.path().join("other"), 0).unwrap();
+        assert_eq!(vec!["1.1.0"], versions());
+        Backup::prune(&backups, "foo", &root, 0).unwrap();
+        assert!(versions().is_empty());
+    }
+}
@@ -1,25 +1,141 @@
-//! Cargo specific logic to parse the binary crate cache located in `$CARGO_HOME/.crates2.json`.
+//! Cargo specific logic to parse the binary crate cache located in `$CARGO_HOME/.crates2.json` (or
+//! its legacy variant `$CARGO_HOME/.crates.toml`), as well as the parts of cargo's own
+//! configuration that are relevant for finding updates.
 
 use std::{
-    collections::{BTreeMap, BTreeSet},
+    collections::{btree_map, BTreeMap, BTreeSet},
+    env,
+    fs::{self, File},
     hash::Hash,
+    io::ErrorKind,
+    path::{Path, PathBuf},
 };
 
 use anyhow::{anyhow, bail, Context, Result};
 use rustc_version::VersionMeta;
-use semver::Version;
+use semver::{Op, Version, VersionReq};
 use serde::{de, Deserialize};
 use url::Url;
 
+/// Location of the main crates.io registry, when accessed through the Git protocol.
+pub const CRATES_IO_INDEX: &str = "https://github.com/rust-lang/crates.io-index";
+/// Location of the main crates.io registry, when accessed through the sparse HTTP protocol.
+pub const CRATES_IO_HTTP_INDEX: &str = "sparse+https://index.crates.io/";
+
 /// Tracking information for the set of installed packages.
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Default, Deserialize)]
 pub struct CrateListingV2 {
     /// Map of every installed package.
     pub installs: BTreeMap<PackageId, InstallInfo>,
 }
 
+impl CrateListingV2 {
+    /// Load the listing from the given installation root, if it exists.
+    pub fn load(root: &Path) -> Result<Option<Self>> {
+        let path = root.join(".crates2.json");
+
+        let file = match File::open(&path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(None),
+            Err(e) => {
+                return Err(e).with_context(|| format!("failed opening `{}`", path.display()))
+            }
+        };
+
+        serde_json::from_reader(file)
+            .map(Some)
+            .with_context(|| format!("failed parsing `{}`", path.display()))
+    }
+
+    /// Merge the packages of the legacy listing into this one. Packages that are only present in
+    /// the legacy listing are added with default installation settings.
+    ///
+    /// All packages where the listings disagree are reported back.
+    pub fn merge_v1(&mut self, v1: CrateListingV1) -> Vec<(String, ListingMismatch)> {
+        let mut mismatches = Vec::new();
+
+        for (package, info) in &self.installs {
+            match v1.v1.get(package) {
+                Some(bins) if *bins != info.bins => {
+                    mismatches.push((package.name.clone(), ListingMismatch::Bins));
+                }
+                Some(_) => {}
+                None => mismatches.push((package.name.clone(), ListingMismatch::MissingV1)),
+            }
+        }
+
+        for (package, bins) in v1.v1 {
+            if let btree_map::Entry::Vacant(entry) = self.installs.entry(package) {
+                mismatches.push((entry.key().name.clone(), ListingMismatch::MissingV2));
+                entry.insert(InstallInfo {
+                    bins,
+                    ..InstallInfo::default()
+                });
+            }
+        }
+
+        mismatches
+    }
+
+    /// Assign the names of alternative registries to all packages that came from one of them, as
+    /// the listing only records the registry URL, but the name is needed to install from it again.
+    pub fn assign_registry_names(&mut self, config: &Config) {
+        self.installs = std::mem::take(&mut self.installs)
+            .into_iter()
+            .map(|(mut package, info)| {
+                if package.source_id.kind == SourceKind::Registry
+                    && !package.source_id.is_crates_io()
+                {
+                    package.source_id.name = config
+                        .registry_name(&package.source_id)
+                        .map(ToOwned::to_owned);
+                }
+                (package, info)
+            })
+            .collect();
+    }
+}
+
+/// Legacy tracking information for the set of installed packages, that only records the installed
+/// binaries for each package.
+#[derive(Debug, Default, Deserialize)]
+pub struct CrateListingV1 {
+    /// Map of every installed package to its binaries.
+    v1: BTreeMap<PackageId, BTreeSet<String>>,
+}
+
+impl CrateListingV1 {
+    /// Load the legacy listing from the given installation root, if it exists.
+    pub fn load(root: &Path) -> Result<Option<Self>> {
+        let path = root.join(".crates.toml");
+
+        let content = match fs::read_to_string(&path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(None),
+            Err(e) => {
+                return Err(e).with_context(|| format!("failed reading `{}`", path.display()))
+            }
+        };
+
+        toml::from_str(&content)
+            .map(Some)
+            .with_context(|| format!("failed parsing `{}`", path.display()))
+    }
+}
+
+/// Disagreement between the current and legacy listing about a single package.
+#[derive(Debug, PartialEq, Eq)]
+pub enum ListingMismatch {
+    /// Package is missing from the legacy listing.
+    MissingV1,
+    /// Package is missing from the current listing.
+    MissingV2,
+    /// Both listings contain the package, but with differing binaries.
+    Bins,
+}
+
 /// Identifier for a specific version of a package in a specific source.
-#[derive(Debug, PartialOrd, Ord, PartialEq, Eq)]
+#[derive(Clone, Debug, PartialOrd, Ord, PartialEq, Eq)]
 pub struct PackageId {
     /// Identifier of the package from its `Cargo.toml`.
     pub name: String,
@@ -62,7 +178,7 @@ impl<'de> de::Deserialize<'de> for PackageId {
 }
 
 /// Unique identifier for a source of packages.
-#[derive(Debug, PartialOrd, Ord, PartialEq, Eq)]
+#[derive(Clone, Debug, PartialOrd, Ord, PartialEq, Eq)]
 pub struct SourceId {
     /// The source URL.
     pub url: Url,
@@ -139,6 +255,13 @@ impl SourceId {
         }
     }
 
+    /// Whether this source is the main crates.io registry, regardless of the protocol used to
+    /// access it.
+    pub fn is_crates_io(&self) -> bool {
+        self.kind == SourceKind::Registry
+            && matches!(self.url.as_str(), CRATES_IO_INDEX | CRATES_IO_HTTP_INDEX)
+    }
+
     fn for_git(url: &Url, reference: GitReference) -> Result<SourceId> {
         SourceId::new(SourceKind::Git(reference), url.clone(), None)
     }
@@ -150,7 +273,7 @@ impl SourceId {
 
 /// The possible kinds of code source. Along with `SourceIdInner`, this fully defines the
 /// source.
-#[derive(Debug, PartialOrd, Ord, PartialEq, Eq)]
+#[derive(Clone, Debug, PartialOrd, Ord, PartialEq, Eq)]
 pub enum SourceKind {
     /// A git repository.
     Git(GitReference),
@@ -161,7 +284,7 @@ pub enum SourceKind {
 }
 
 /// Information to find a specific commit in a Git repository.
-#[derive(Debug, PartialOrd, Ord, PartialEq, Eq)]
+#[derive(Clone, Debug, PartialOrd, Ord, PartialEq, Eq)]
 pub enum GitReference {
     /// From a tag.
     Tag(String),
@@ -176,7 +299,7 @@ pub enum GitReference {
 /// The canonical URL is a sanitized version of the original URL, providing a stable version that
 /// can be used for hashing, which is in turn used to determine the folder of Git repositories as
 /// used by `cargo`.
-#[derive(Debug, PartialOrd, Ord, PartialEq, Eq, Hash)]
+#[derive(Clone, Debug, PartialOrd, Ord, PartialEq, Eq, Hash)]
 pub struct CanonicalUrl(pub Url);
 
 impl CanonicalUrl {
@@ -237,8 +360,9 @@ impl CanonicalUrl {
 
 /// Tracking information for the installation of a single package. This tracks the settings that
 /// were used when the package was installed.
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Default, Deserialize)]
 pub struct InstallInfo {
+    /// Version requirement that was given when installing, like `cargo install foo@^1`.
     pub version_req: Option<String>,
     /// Set of binary names installed.
     pub bins: BTreeSet<String>,
@@ -255,6 +379,228 @@ pub struct InstallInfo {
     /// Output of `rustc -V --verbose`.
     #[serde(deserialize_with = "deser::version_meta")]
     pub rustc: Option<VersionMeta>,
+    /// Installation root that the package was found in. This is not part of the listing, but set
+    /// once it was loaded.
+    #[serde(skip)]
+    pub root: PathBuf,
+}
+
+/// The parts of cargo's configuration file `$CARGO_HOME/config.toml`, that are relevant for
+/// locating crate sources.
+#[derive(Debug, Default, Deserialize)]
+pub struct Config {
+    /// Alternative registries, keyed by their name.
+    #[serde(default)]
+    pub registries: BTreeMap<String, RegistryConfig>,
+    /// Settings for `cargo install`.
+    #[serde(default)]
+    pub install: InstallConfig,
+    /// Settings for building crates.
+    #[serde(default)]
+    pub build: BuildConfig,
+}
+
+/// Configuration for building crates.
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct BuildConfig {
+    /// Directory to put build artifacts into, that is shared by all builds.
+    pub target_dir: Option<PathBuf>,
+}
+
+/// Configuration for the `cargo install` command.
+#[derive(Debug, Default, Deserialize)]
+pub struct InstallConfig {
+    /// Directory to install binaries into, instead of cargo's home directory.
+    pub root: Option<PathBuf>,
+}
+
+/// Configuration for a single alternative registry, or the main crates.io registry under the
+/// special name `crates-io`.
+#[derive(Debug, Default, Deserialize)]
+pub struct RegistryConfig {
+    /// Location of the registry index, either a Git URL or a `sparse+` prefixed HTTP URL.
+    pub index: Option<String>,
+    /// Protocol used to access the index. Only relevant for the crates.io registry.
+    pub protocol: Option<Protocol>,
+}
+
+/// Protocol to access a registry index with.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Protocol {
+    /// Clone the full index as Git repository.
+    Git,
+    /// Fetch only the required index entries over HTTP.
+    Sparse,
+}
+
+impl Config {
+    /// Load the configuration from cargo's home directory, falling back to the legacy file name
+    /// without extension. A missing file results in the default configuration.
+    ///
+    /// Only the file in cargo's home directory is read, as installed binaries don't belong to any
+    /// project, so configuration files in the current directory or its parents are ignored. The
+    /// index locations of registries can still be overridden with `CARGO_REGISTRIES_<NAME>_INDEX`
+    /// environment variables, like in cargo itself.
+    pub fn load() -> Result<Self> {
+        let mut config = Self::load_file()?;
+        config.apply_env(
+            env::vars_os().filter_map(|(key, value)| {
+                Some((key.into_string().ok()?, value.into_string().ok()?))
+            }),
+        );
+
+        Ok(config)
+    }
+
+    fn load_file() -> Result<Self> {
+        let home = home::cargo_home()?;
+
+        for name in ["config.toml", "config"] {
+            let path = home.join(name);
+            if path.is_file() {
+                let content = fs::read_to_string(&path)?;
+                return toml::from_str(&content)
+                    .with_context(|| format!("failed parsing `{}`", path.display()));
+            }
+        }
+
+        Ok(Self::default())
+    }
+
+    /// Override the registry indexes with the given environment variables. Cargo derives the
+    /// variable name from the registry name in uppercase with dashes replaced by underscores, so
+    /// registries that are only defined this way get the lowercase form as name.
+    fn apply_env(&mut self, vars: impl IntoIterator<Item = (String, String)>) {
+        for (key, value) in vars {
+            let Some(env_name) = key
+                .strip_prefix("CARGO_REGISTRIES_")
+                .and_then(|key| key.strip_suffix("_INDEX"))
+            else {
+                continue;
+            };
+
+            let name = self
+                .registries
+                .keys()
+                .find(|name| name.to_uppercase().replace('-', "_") == env_name)
+                .cloned()
+                .unwrap_or_else(|| env_name.to_lowercase());
+
+            self.registries.entry(name).or_default().index = Some(value);
+        }
+    }
+
+    /// Determine the installation root the same way cargo does, in order of precedence from the
+    /// `CARGO_INSTALL_ROOT` environment variable, the `install.root` setting or cargo's home
+    /// directory.
+    pub fn install_root(&self) -> Result<PathBuf> {
+        if let Some(root) = env::var_os("CARGO_INSTALL_ROOT") {
+            return Ok(root.into());
+        }
+
+        let home = home::cargo_home()?;
+
+        Ok(match &self.install.root {
+            // Relative paths are relative to the parent of the directory containing the config
+            // file, which is the cargo home directory in this case.
+            Some(root) if root.is_relative() => home
+                .parent()
+                .map_or_else(|| root.clone(), |parent| parent.join(root)),
+            Some(root) => root.clone(),
+            None => home,
+        })
+    }
+
+    /// Determine the target directory that cargo is configured to share between all builds, either
+    /// by the `CARGO_TARGET_DIR` environment variable or the `build.target-dir` setting. By
+    /// default, `cargo install` uses a new temporary directory for each build instead.
+    pub fn target_dir(&self) -> Result<Option<PathBuf>> {
+        if let Some(dir) = env::var_os("CARGO_TARGET_DIR") {
+            return Ok(Some(dir.into()));
+        }
+
+        let home = home::cargo_home()?;
+
+        Ok(self.build.target_dir.as_ref().map(|dir| {
+            // Relative the same way as the installation root.
+            if dir.is_relative() {
+                home.parent()
+                    .map_or_else(|| dir.clone(), |parent| parent.join(dir))
+            } else {
+                dir.clone()
+            }
+        }))
+    }
+
+    /// Determine the protocol that cargo uses for the crates.io registry, which is the sparse
+    /// protocol unless configured otherwise.
+    pub fn crates_io_protocol(&self) -> Result<Protocol> {
+        if let Ok(value) = env::var("CARGO_REGISTRIES_CRATES_IO_PROTOCOL") {
+            return match value.as_str() {
+                "git" => Ok(Protocol::Git),
+                "sparse" => Ok(Protocol::Sparse),
+                _ => bail!(
+                    "invalid value `{value}` for CARGO_REGISTRIES_CRATES_IO_PROTOCOL, expected \
+                     `git` or `sparse`"
+                ),
+            };
+        }
+
+        Ok(self
+            .registries
+            .get("crates-io")
+            .and_then(|registry| registry.protocol)
+            .unwrap_or(Protocol::Sparse))
+    }
+
+    /// Find the name of the alternative registry, that is located at the same index as the
+    /// given source.
+    pub fn registry_name(&self, source_id: &SourceId) -> Option<&str> {
+        self.registries.iter().find_map(|(name, registry)| {
+            let url = Url::parse(registry.index.as_deref()?).ok()?;
+            let url = CanonicalUrl::new(&url).ok()?;
+            (url == source_id.canonical_url).then_some(name.as_str())
+        })
+    }
+}
+
+impl InstallInfo {
+    /// Parse the recorded version requirement, the same way cargo interprets the `--version`
+    /// argument. That means a plain version without any operator is an exact requirement.
+    ///
+    /// An upper bound at the installed version is dropped, as that's how an update is capped below
+    /// a version that needs a newer toolchain, and it would hold back any later updates otherwise.
+    pub fn version_req(&self, installed: &Version) -> Result<Option<VersionReq>> {
+        let Some(req) = self.version_req.as_deref() else {
+            return Ok(None);
+        };
+
+        let mut req = match Version::parse(req) {
+            Ok(version) => VersionReq::parse(&format!("={version}"))?,
+            Err(_) => VersionReq::parse(req)
+                .with_context(|| format!("invalid version requirement `{req}`"))?,
+        };
+
+        if req.comparators.len() > 1 {
+            req.comparators.retain(|c| {
+                c.op != Op::LessEq
+                    || c.major != installed.major
+                    || c.minor != Some(installed.minor)
+                    || c.patch != Some(installed.patch)
+                    || c.pre != installed.pre
+            });
+        }
+
+        Ok(Some(req))
+    }
+
+    /// The recorded installation target, if it differs from the given host triple. Cargo records
+    /// the host as target as well, if none was specified.
+    pub fn cross_target(&self, host: &str) -> Option<&str> {
+        self.target.as_deref().filter(|target| *target != host)
+    }
 }
 
 mod deser {
@@ -274,3 +620,144 @@ mod deser {
             .transpose()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn find_registry_name() {
+        let config = toml::from_str::<Config>(
+            r#"
+            [registries]
+            company = { index = "https://git.example.com/index.git" }
+            company-sparse = { index = "sparse+https://crates.example.com/index/" }
+            "#,
+        )
+        .unwrap();
+
+        let git = SourceId::from_url("registry+https://git.example.com/index").unwrap();
+        assert_eq!(Some("company"), config.registry_name(&git));
+
+        let sparse = SourceId::from_url("sparse+https://crates.example.com/index/").unwrap();
+        assert_eq!(Some("company-sparse"), config.registry_name(&sparse));
+
+        let crates_io = SourceId::from_url(&format!("registry+{CRATES_IO_INDEX}")).unwrap();
+        assert!(crates_io.is_crates_io());
+        assert_eq!(None, config.registry_name(&crates_io));
+    }
+
+    #[test]
+    fn registry_env_overrides() {
+        let mut config = toml::from_str::<Config>(
+            r#"
+            [registries]
+            company-sparse = { index = "sparse+https://old.example.com/index/" }
+            "#,
+        )
+        .unwrap();
+
+        config.apply_env([
+            (
+                "CARGO_REGISTRIES_COMPANY_SPARSE_INDEX".to_owned(),
+                "sparse+https://crates.example.com/index/".to_owned(),
+            ),
+            (
+                "CARGO_REGISTRIES_OTHER_INDEX".to_owned(),
+                "https://git.example.com/index.git".to_owned(),
+            ),
+            (
+                "CARGO_REGISTRIES_CRATES_IO_PROTOCOL".to_owned(),
+                "git".to_owned(),
+            ),
+        ]);
+
+        let sparse = SourceId::from_url("sparse+https://crates.example.com/index/").unwrap();
+        assert_eq!(Some("company-sparse"), config.registry_name(&sparse));
+
+        let git = SourceId::from_url("registry+https://git.example.com/index").unwrap();
+        assert_eq!(Some("other"), config.registry_name(&git));
+
+        assert_eq!(2, config.registries.len());
+    }
+
+    #[test]
+    fn parse_version_req() {
+        let info = |req: &str| InstallInfo {
+            version_req: Some(req.to_owned()),
+            ..InstallInfo::default()
+        };
+
+        let installed = Version::new(1, 2, 3);
+
+        // A plain version is an exact requirement, as in `cargo install --version`
+        let req = info("1.2.3").version_req(&installed).unwrap().unwrap();
+        assert!(req.matches(&Version::new(1, 2, 3)));
+        assert!(!req.matches(&Version::new(1, 2, 4)));
+
+        let req = info("^1").version_req(&installed).unwrap().unwrap();
+        assert!(req.matches(&Version::new(1, 9, 0)));
+        assert!(!req.matches(&Version::new(2, 0, 0)));
+
+        // The cap of an update is dropped again, but other upper bounds are kept.
+        let req = info("^1, <=1.2.3")
+            .version_req(&installed)
+            .unwrap()
+            .unwrap();
+        assert!(req.matches(&Version::new(1, 9, 0)));
+        assert!(!req.matches(&Version::new(2, 0, 0)));
+
+        let req = info(">=1, <=1.5.0")
+            .version_req(&installed)
+            .unwrap()
+            .unwrap();
+        assert!(!req.matches(&Version::new(1, 9, 0)));
+    }
+
+    #[test]
+    fn cross_compiled_target() {
+        let info = |target: Option<&str>| InstallInfo {
+            target: target.map(ToOwned::to_owned),
+            ..InstallInfo::default()
+        };
+        let host = "x86_64-unknown-linux-gnu";
+
+        assert_eq!(None, info(None).cross_target(host));
+        assert_eq!(None, info(Some(host)).cross_target(host));
+        assert_eq!(
+            Some("x86_64-unknown-linux-musl"),
+            info(Some("x86_64-unknown-linux-musl")).cross_target(host)
+        );
+    }
+
+    #[test]
+    fn merge_legacy_listing() {
+        let mut v2 = serde_json::from_str::<CrateListingV2>(
+            r#"{"installs":{
+                "a 1.0.0 (registry+https://github.com/rust-lang/crates.io-index)":{"version_req":null,"bins":["a"],"features":[],"all_features":false,"no_default_features":false,"profile":"release","target":null,"rustc":null},
+                "b 1.0.0 (registry+https://github.com/rust-lang/crates.io-index)":{"version_req":null,"bins":["b"],"features":[],"all_features":false,"no_default_features":false,"profile":"release","target":null,"rustc":null}
+            }}"#,
+        )
+        .unwrap();
+        let v1 = toml::from_str::<CrateListingV1>(
+            r#"
+            [v1]
+            "b 1.0.0 (registry+https://github.com/rust-lang/crates.io-index)" = ["b", "b2"]
+            "c 1.0.0 (registry+https://github.com/rust-lang/crates.io-index)" = ["c"]
+            "#,
+        )
+        .unwrap();
+
+        let mismatches = v2.merge_v1(v1);
+
+        assert_eq!(
+            vec![
+                ("a".to_owned(), ListingMismatch::MissingV1),
+                ("b".to_owned(), ListingMismatch::Bins),
+                ("c".to_owned(), ListingMismatch::MissingV2),
+            ],
+            mismatches
+        );
+        assert_eq!(3, v2.installs.len());
+    }
+}
@@ -1,8 +1,12 @@
 //! Command line interface related logic.
 
-use clap::{Args, CommandFactory, Parser, Subcommand};
+use std::path::PathBuf;
+
+use clap::{Args, CommandFactory, Parser, Subcommand, ValueEnum};
 use clap_complete::Shell;
 
+use crate::{cargo::Protocol, models::UpdateLevel};
+
 #[derive(Parser)]
 #[command(name = "cargo", bin_name = "cargo")]
 enum Cli {
@@ -12,17 +16,88 @@ enum Cli {
 
 /// Update your cargo-installed binaries.
 #[derive(Parser)]
-#[command(about, author, version)]
+#[command(
+    about,
+    author,
+    version,
+    after_help = "Exit codes:
+  0  Everything is up-to-date, or all updates were installed successfully
+  1  Checking for updates failed
+  3  Updates are available (with --dry-run and --exit-code)
+  4  Installing at least one of the updates failed
+  5  An installed version has been yanked (with --fail-on-yanked)
+
+Settings:
+  Single crates can be ignored or pinned to a version requirement, and defaults
+  for the cargo install options can be set, in either
+  `$XDG_CONFIG_HOME/cargo-bup/config.toml` or `$CARGO_HOME/bup.toml`."
+)]
 pub struct Command {
     /// Arguments focused around selecting different kind of updates.
     #[command(flatten)]
     pub select_args: SelectArgs,
+    /// Arguments that are passed on to `cargo install`.
+    #[command(flatten)]
+    pub install_args: InstallArgs,
     /// Do an update check, but don't start any actual update installations.
     #[arg(short = 'n', long)]
     pub dry_run: bool,
+    /// Exit with a distinct status code, if any updates are available during a dry run.
+    ///
+    /// Similar to `git diff --exit-code`, this allows to check for outstanding updates in
+    /// scripts.
+    #[arg(long, requires = "dry_run")]
+    pub exit_code: bool,
+    /// Choose the updates to install from a checklist, before installing any of them.
+    ///
+    /// Registry, git and path updates as well as rebuilds are listed, with breaking registry
+    /// updates unselected by default. Cancelling the selection installs nothing. Ignored if the
+    /// standard output isn't a terminal.
+    #[arg(short = 'i', long, conflicts_with = "dry_run")]
+    pub interactive: bool,
+    /// Exit with a non-zero status code, if the installed version of any binary has been yanked
+    /// from its registry.
+    #[arg(long)]
+    pub fail_on_yanked: bool,
     /// Hide the cargo build output.
+    ///
+    /// The output is written to a log file in `$CARGO_HOME/bup/logs` instead, which is pointed to
+    /// for any failed installation.
     #[arg(short = 'q', long)]
     pub quiet: bool,
+    /// Run each updated binary with `--version`, and roll it back if that fails.
+    ///
+    /// Other arguments can be set with `smoke-test-args` in the settings. The previous version is
+    /// restored from the backup that is taken before every update. See the `rollback` command for
+    /// restoring a backup manually.
+    #[arg(long)]
+    pub smoke_test: bool,
+    /// Number of updates to install at the same time.
+    ///
+    /// With more than one job, the build output of each crate is written to a log file in
+    /// `$CARGO_HOME/bup/logs` instead, and only its progress is shown.
+    #[arg(long, value_name = "N", default_value_t = 1, value_parser = clap::value_parser!(u16).range(1..))]
+    pub jobs: u16,
+    /// Installation root to check for installed binaries, instead of the default one.
+    ///
+    /// Can be given multiple times to check several roots at once. By default, the same root as
+    /// for `cargo install` is used, which is either `CARGO_INSTALL_ROOT`, the `install.root`
+    /// setting in cargo's configuration, or cargo's home directory.
+    #[arg(long, value_name = "DIR")]
+    pub root: Vec<PathBuf>,
+    /// Protocol to access the crates.io index with.
+    ///
+    /// Defaults to the protocol that cargo itself uses, as configured by the
+    /// `registries.crates-io.protocol` setting, which is the sparse protocol unless configured
+    /// otherwise.
+    #[arg(long, value_enum)]
+    pub protocol: Option<IndexProtocol>,
+    /// Output format of the update report.
+    ///
+    /// The machine readable formats print the report to the standard output, while any progress
+    /// messages and cargo's build output are written to the standard error stream.
+    #[arg(long, value_enum, default_value_t = Format::Human)]
+    pub format: Format,
     /// Optional sub-commands that can be triggered.
     #[command(subcommand)]
     pub subcmd: Option<Subcmd>,
@@ -32,22 +107,157 @@ pub struct Command {
 /// are allowed, as some of these might take extra time to check.
 #[derive(Args)]
 pub struct SelectArgs {
+    /// Only check and update the given crates, instead of all installed ones.
+    ///
+    /// Names may contain glob patterns like `cargo-*`, which should be quoted to prevent the shell
+    /// from expanding them.
+    #[arg(value_name = "CRATE")]
+    pub crates: Vec<String>,
+    /// Skip the given crates, even if selected otherwise.
+    ///
+    /// Like the positional crate names, this may contain glob patterns.
+    #[arg(long, value_name = "CRATE", value_delimiter = ',')]
+    pub exclude: Vec<String>,
     /// Include pre-releases in updates.
     #[arg(long)]
     pub pre: bool,
+    /// Only install registry updates up to the given level, holding back any bigger ones.
+    ///
+    /// Held back updates are still listed, but not installed. Following cargo's flavor of semantic
+    /// versioning, the left-most non-zero version component is considered the major version.
+    #[arg(long, value_enum, value_name = "LEVEL")]
+    pub only: Option<UpdateLevel>,
+    /// Only offer updates that match the version requirement recorded at install time.
+    ///
+    /// For example, a crate installed with `cargo install foo@^1` won't be updated to version 2.x
+    /// anymore. Without any value, this applies to all crates, otherwise only to the given ones.
+    /// It can also be enabled for single crates with the `respect-req` setting.
+    #[arg(
+        long,
+        value_name = "CRATE",
+        num_args = 0..=1,
+        require_equals = true,
+        value_delimiter = ','
+    )]
+    pub respect_req: Option<Vec<String>>,
+    /// Offer updates regardless of the minimum Rust version they require.
+    ///
+    /// By default, versions that declare a `rust-version` newer than the active `rustc` are
+    /// skipped in favor of the newest compatible version.
+    #[arg(long)]
+    pub ignore_rust_version: bool,
+    /// Rebuild binaries that were built with an older Rust toolchain than the active one.
+    ///
+    /// Binaries without any available update are re-installed at their current version, from the
+    /// same source they were installed from. Crates installed from a Git branch are only rebuilt in
+    /// combination with `--git`, which ensures the branch has no new commits.
+    #[arg(long)]
+    pub rebuild_outdated_toolchain: bool,
     /// Include crates installed from git repos (potentially slow).
     ///
     /// To find updates, each crate's local Git repository is updated against the remote repo.
     #[arg(long)]
     pub git: bool,
+    /// Move crates installed from a Git tag to the newest tag with a greater version.
+    ///
+    /// Tags are expected to contain a SemVer version, like `v1.2.3`. Only has an effect in
+    /// combination with `--git`.
+    #[arg(long)]
+    pub follow_tags: bool,
+    /// Install a crate that is pinned to a Git revision from the default branch instead.
+    ///
+    /// Crates installed with `--rev` are only reported when their revision is behind the remote
+    /// default branch. This moves the given crates to the latest commit of that branch, which
+    /// implies `--git` for them.
+    #[arg(long, value_name = "CRATE", value_delimiter = ',')]
+    pub unpin: Vec<String>,
     /// Include crates installed from local paths (potentially slow).
     ///
-    /// There is no way of checking the freshness for a crate that was installed locally, so cargo
-    /// is invoked for each entry unconditionally.
+    /// A crate is considered changed, if any of the files in its directory were modified after
+    /// its binaries were installed.
     #[arg(long)]
     pub path: bool,
 }
 
+impl SelectArgs {
+    /// Whether updates for the given crate should be restricted to its recorded version
+    /// requirement.
+    pub fn respects_req(&self, name: &str) -> bool {
+        self.respect_req
+            .as_ref()
+            .is_some_and(|names| names.is_empty() || names.iter().any(|n| n == name))
+    }
+
+    /// Whether the given crate should be moved from its pinned revision to the default branch.
+    pub fn unpins(&self, name: &str) -> bool {
+        self.unpin.iter().any(|n| n == name)
+    }
+}
+
+/// Arguments that are passed on to every `cargo install` invocation, in addition to the ones that
+/// were recorded when the crate was installed.
+#[derive(Args)]
+#[command(next_help_heading = "Cargo install options")]
+pub struct InstallArgs {
+    /// Install with the exact dependency versions from each crate's `Cargo.lock`.
+    #[arg(long)]
+    pub locked: bool,
+    /// Build without accessing the network.
+    ///
+    /// This only applies to `cargo install`. The update check still needs to access the
+    /// registries.
+    #[arg(long)]
+    pub offline: bool,
+    /// Overwrite existing binaries, even if they weren't installed by the same crate.
+    #[arg(long)]
+    pub force: bool,
+    /// Number of parallel jobs to build each crate with, like `cargo install --jobs`.
+    #[arg(long, value_name = "N")]
+    pub build_jobs: Option<u32>,
+    /// Override a cargo configuration value, or load an extra configuration file.
+    ///
+    /// Can be given multiple times, like for `cargo install --config`.
+    #[arg(long, value_name = "KEY=VALUE|PATH")]
+    pub config: Vec<String>,
+    /// Directory for all build artifacts, instead of a new temporary one for each crate.
+    ///
+    /// With more than one concurrent job, each crate gets its own sub-directory.
+    #[arg(long, value_name = "DIR")]
+    pub target_dir: Option<PathBuf>,
+    /// Further arguments to pass on to `cargo install`.
+    #[arg(last = true, value_name = "CARGO_ARGS")]
+    pub cargo_args: Vec<String>,
+}
+
+/// Protocol to access the crates.io index with.
+#[derive(Clone, Copy, ValueEnum)]
+pub enum IndexProtocol {
+    /// Clone the full index as Git repository.
+    Git,
+    /// Fetch only the required index entries over HTTP.
+    Sparse,
+}
+
+impl From<IndexProtocol> for Protocol {
+    fn from(value: IndexProtocol) -> Self {
+        match value {
+            IndexProtocol::Git => Self::Git,
+            IndexProtocol::Sparse => Self::Sparse,
+        }
+    }
+}
+
+/// Possible formats for the update report.
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Format {
+    /// Colored tables, for humans to read.
+    Human,
+    /// A single JSON document, including the results of all installations.
+    Json,
+    /// One JSON object per line, for each update and installation result.
+    Ndjson,
+}
+
 /// Any sub-commands that are trigger extra behavior, not part of the main function of this plugin.
 #[derive(Subcommand)]
 pub enum Subcmd {
@@ -57,6 +267,37 @@ pub enum Subcmd {
         #[arg(value_enum)]
         shell: Shell,
     },
+    /// Show the log of past installations, oldest first.
+    History(HistoryArgs),
+    /// Restore the previous version of a crate, from the backup taken before its last update.
+    ///
+    /// Backups are kept in `$CARGO_HOME/bup/backups`, with the last three versions per crate by
+    /// default. Rolling back repeatedly restores older versions.
+    Rollback {
+        /// Name of the crate to roll back.
+        #[arg(value_name = "CRATE")]
+        name: String,
+    },
+}
+
+/// Arguments for filtering the installation history.
+#[derive(Args)]
+pub struct HistoryArgs {
+    /// Only show installations of the given crates, which may contain glob patterns.
+    #[arg(value_name = "CRATE")]
+    pub crates: Vec<String>,
+    /// Only show failed installations.
+    #[arg(long)]
+    pub failed: bool,
+    /// Only show installations from the last given number of days.
+    #[arg(long, value_name = "DAYS")]
+    pub since: Option<u64>,
+    /// Maximum number of installations to show, starting from the most recent one.
+    #[arg(short = 'n', long, value_name = "N", default_value_t = 20)]
+    pub limit: usize,
+    /// Output format of the history.
+    #[arg(long, value_enum, default_value_t = Format::Human)]
+    pub format: Format,
 }
 
 /// Parse the CLI arguments, returning them as well structured data.
@@ -30,13 +30,18 @@ impl<T> Styled<T> {
 
 impl<T: Display> Display for Styled<T> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(
-            f,
-            "{}{}{}",
-            self.style.render(),
-            self.display,
-            self.style.render_reset()
-        )
+        write!(f, "{}{}", self.style.render(), self.display)?;
+
+        // Only reset the foreground color if nothing else was set, to not interfere with any
+        // surrounding styles.
+        if self.style.get_effects().is_plain() && self.style.get_bg_color().is_none() {
+            if self.style.get_fg_color().is_some() {
+                f.write_str("\x1b[39m")?;
+            }
+            Ok(())
+        } else {
+            write!(f, "{}", self.style.render_reset())
+        }
     }
 }
 
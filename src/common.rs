@@ -1,11 +1,19 @@
-use std::process::{Command, Stdio};
+use std::{
+    fs::File,
+    hash::{Hash, Hasher},
+    io::{self, Read, Write},
+    path::{Path, PathBuf},
+    process::{Command, ExitStatus, Stdio},
+    time::{Duration, Instant},
+};
 
-use anstream::eprintln;
-use anyhow::Result;
+use anstream::{adapter, AutoStream, ColorChoice};
+use anyhow::{Context, Result};
+use siphasher::sip::SipHasher24;
 
 use crate::cargo::InstallInfo;
 
-pub fn apply_cmd_args(cmd: &mut Command, info: &InstallInfo) {
+pub fn apply_cmd_args(cmd: &mut Command, info: &InstallInfo, host: &str) {
     for bin in &info.bins {
         cmd.args(["--bin", bin]);
     }
@@ -30,24 +38,197 @@ pub fn apply_cmd_args(cmd: &mut Command, info: &InstallInfo) {
     if !info.profile.is_empty() {
         cmd.args(["--profile", &info.profile]);
     }
+
+    // Cargo builds for the host by default, which would silently replace cross-compiled binaries.
+    if let Some(target) = info.cross_target(host) {
+        cmd.args(["--target", target]);
+    }
+
+    // Always install into the root the package was found in, regardless of cargo's default.
+    cmd.arg("--root");
+    cmd.arg(&info.root);
+}
+
+/// Short hash of the installation root, to keep the files of the same crate in different roots
+/// apart.
+pub fn root_hash(root: &Path) -> String {
+    let mut hasher = SipHasher24::new();
+    root.hash(&mut hasher);
+    hex::encode(&hasher.finish().to_le_bytes()[..4])
 }
 
-pub fn run_cmd(mut cmd: Command, name: &str, quiet: bool) -> Result<()> {
-    if !quiet {
-        cmd.stdout(Stdio::inherit());
-        cmd.stderr(Stdio::inherit());
+/// Outcome of a single cargo invocation.
+pub struct CmdOutput {
+    pub status: ExitStatus,
+    /// Time it took cargo to finish.
+    pub duration: Duration,
+    /// Cargo's error output, with any styling removed. Empty if the output was inherited.
+    pub stderr: String,
+    /// File that cargo's full output was written to, if any.
+    pub log: Option<PathBuf>,
+}
+
+/// Destination for cargo's output, while it's running.
+pub enum Output<'a> {
+    /// Let cargo write to the standard streams directly, so it can show its progress bar. The
+    /// error output isn't captured in this case.
+    Inherit,
+    /// Forward all output to the standard error stream, keeping the standard output free.
+    Stderr,
+    /// Write the output to the given file, replacing any previous content.
+    Log(&'a Path),
+}
+
+/// Run the cargo command and wait for it to finish. Cargo's output is forwarded to the given
+/// destination, and the error output is captured unless it's inherited.
+///
+/// Only the failure to run cargo at all is returned as error, whereas a failed installation is
+/// reported through the returned [`CmdOutput`].
+pub fn run_cmd(mut cmd: Command, output: Output<'_>) -> Result<CmdOutput> {
+    let mut log = None;
+
+    match output {
+        Output::Inherit => {
+            let start = Instant::now();
+            let status = cmd.status()?;
+
+            return Ok(CmdOutput {
+                status,
+                duration: start.elapsed(),
+                stderr: String::new(),
+                log: None,
+            });
+        }
+        Output::Stderr => {
+            cmd.stdout(io::stderr());
+
+            // Cargo can't detect the terminal anymore, as its output is piped through. A color
+            // choice of the user still takes precedence.
+            if std::env::var_os("CARGO_TERM_COLOR").is_none()
+                && AutoStream::choice(&io::stderr()) != ColorChoice::Never
+            {
+                cmd.env("CARGO_TERM_COLOR", "always");
+            }
+        }
+        Output::Log(path) => {
+            let file = File::create(path)
+                .with_context(|| format!("failed creating log file `{}`", path.display()))?;
+            cmd.stdout(file.try_clone()?);
+            log = Some((file, path.to_owned()));
+        }
+    }
+
+    cmd.stderr(Stdio::piped());
+
+    let start = Instant::now();
+    let mut child = cmd.spawn()?;
+    let mut stderr = child.stderr.take().context("missing cargo output")?;
+
+    let mut captured = Vec::new();
+    let mut buf = [0; 4096];
+
+    loop {
+        let n = stderr.read(&mut buf)?;
+        if n == 0 {
+            break;
+        }
+
+        match (&output, &mut log) {
+            (Output::Stderr, _) => {
+                let mut out = io::stderr().lock();
+                out.write_all(&buf[..n])?;
+                out.flush()?;
+            }
+            (_, Some((file, _))) => file.write_all(&buf[..n])?,
+            _ => {}
+        }
+
+        captured.extend_from_slice(&buf[..n]);
     }
 
-    let output = cmd.output()?;
+    let status = child.wait()?;
+
+    Ok(CmdOutput {
+        status,
+        duration: start.elapsed(),
+        stderr: adapter::strip_str(&String::from_utf8_lossy(&captured)).to_string(),
+        log: log.map(|(_, path)| path),
+    })
+}
+
+/// Extract the first error message from cargo's output, including the `Caused by:` blocks that
+/// follow it, or the last few lines if there is none.
+pub fn error_lines(stderr: &str) -> Vec<String> {
+    const MAX_LINES: usize = 8;
+
+    let lines = stderr.lines().collect::<Vec<_>>();
 
-    if !output.status.success() {
-        if quiet {
-            let stderr = String::from_utf8_lossy(&output.stderr);
-            eprintln!("\nfailed installing `{name}`:\n{stderr}")
-        } else {
-            eprintln!("\nfailed installing `{name}`");
+    match lines.iter().position(|line| line.starts_with("error")) {
+        Some(start) => {
+            let mut message = Vec::new();
+            let mut rest = lines[start..].iter().peekable();
+
+            while let Some(line) = rest.next() {
+                if line.trim().is_empty() {
+                    // Causes are separated from the error by a blank line.
+                    if rest
+                        .peek()
+                        .is_some_and(|next| next.starts_with("Caused by:"))
+                    {
+                        continue;
+                    }
+                    break;
+                }
+                message.push((*line).to_owned());
+            }
+
+            message.truncate(MAX_LINES);
+            message
+        }
+        None => {
+            let lines = lines
+                .iter()
+                .filter(|line| !line.trim().is_empty())
+                .collect::<Vec<_>>();
+
+            lines[lines.len().saturating_sub(MAX_LINES)..]
+                .iter()
+                .map(|line| (**line).to_owned())
+                .collect()
         }
     }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn first_error_lines() {
+        let stderr = "    Updating crates.io index
+  Installing foo v1.2.0
+error: failed to compile `foo v1.2.0`, intermediate artifacts can be found at `/tmp/x`.
 
-    Ok(())
+Caused by:
+  package `bar v0.1.0` cannot be built because it requires rustc 1.80";
+
+        assert_eq!(
+            vec![
+                "error: failed to compile `foo v1.2.0`, intermediate artifacts can be found at `/tmp/x`.",
+                "Caused by:",
+                "  package `bar v0.1.0` cannot be built because it requires rustc 1.80",
+            ],
+            error_lines(stderr)
+        );
+        assert_eq!(
+            vec!["error[E0308]: mismatched types", " --> src/main.rs:2:5"],
+            error_lines(
+                "error[E0308]: mismatched types\n --> src/main.rs:2:5\n\nwarning: unused\n"
+            )
+        );
+        assert_eq!(
+            vec!["  Installing foo v1.2.0", "killed"],
+            error_lines("  Installing foo v1.2.0\nkilled\n\n")
+        );
+    }
 }
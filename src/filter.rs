@@ -0,0 +1,101 @@
+//! Restriction of the installed crates to a subset, selected by their names.
+
+use anyhow::{bail, Context, Result};
+use glob::Pattern;
+
+use crate::cargo::CrateListingV2;
+
+/// Reduce the installed crates to the ones matching any of the included names (or all, if none
+/// are given) and none of the excluded names. Names can be glob patterns like `cargo-*`.
+///
+/// Every name must match at least one installed crate, so typos don't go unnoticed.
+pub(crate) fn apply(
+    info: &mut CrateListingV2,
+    include: &[String],
+    exclude: &[String],
+) -> Result<()> {
+    if include.is_empty() && exclude.is_empty() {
+        return Ok(());
+    }
+
+    let include = compile(info, include)?;
+    let exclude = compile(info, exclude)?;
+
+    info.installs.retain(|pkg, _| {
+        (include.is_empty() || include.iter().any(|p| p.matches(&pkg.name)))
+            && !exclude.iter().any(|p| p.matches(&pkg.name))
+    });
+
+    Ok(())
+}
+
+/// Parse the names as glob patterns, and ensure each of them matches any of the installed crates.
+fn compile(info: &CrateListingV2, names: &[String]) -> Result<Vec<Pattern>> {
+    names
+        .iter()
+        .map(|name| {
+            let pattern =
+                Pattern::new(name).with_context(|| format!("invalid crate pattern `{name}`"))?;
+
+            if !info.installs.keys().any(|pkg| pattern.matches(&pkg.name)) {
+                if name.contains(['*', '?', '[']) {
+                    bail!("no installed crate matches the pattern `{name}`");
+                }
+
+                let similar =
+                    similar_names(name, info.installs.keys().map(|pkg| pkg.name.as_str()));
+                if similar.is_empty() {
+                    bail!("crate `{name}` is not installed");
+                }
+
+                bail!(
+                    "crate `{name}` is not installed, did you mean {}?",
+                    similar
+                        .iter()
+                        .map(|name| format!("`{name}`"))
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                );
+            }
+
+            Ok(pattern)
+        })
+        .collect()
+}
+
+/// Find up to three of the candidates, that are most similar to the given name.
+fn similar_names<'a>(name: &str, candidates: impl Iterator<Item = &'a str>) -> Vec<&'a str> {
+    let mut similar = candidates
+        .map(|candidate| (strsim::jaro_winkler(name, candidate), candidate))
+        .filter(|(score, _)| *score > 0.8)
+        .collect::<Vec<_>>();
+
+    similar.sort_by(|a, b| b.0.total_cmp(&a.0).then(a.1.cmp(b.1)));
+    similar.dedup_by_key(|(_, candidate)| *candidate);
+    similar.truncate(3);
+
+    similar
+        .into_iter()
+        .map(|(_, candidate)| candidate)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn close_matches() {
+        let candidates = ["ripgrep", "bat", "cargo-nextest", "cargo-edit", "ripgrep"];
+
+        assert_eq!(
+            vec!["ripgrep"],
+            similar_names("rigprep", candidates.into_iter())
+        );
+        assert_eq!(
+            vec!["cargo-edit", "cargo-nextest"],
+            similar_names("cargo-edt", candidates.into_iter())
+        );
+        assert!(similar_names("fd", candidates.into_iter()).is_empty());
+    }
+}
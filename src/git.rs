@@ -8,21 +8,30 @@ use std::{
 };
 
 use anstream::println;
-use anyhow::{Context, Result};
+use anyhow::{anyhow, Context, Result};
 use gix::{remote::Direction, Commit, ObjectId, Repository};
+use semver::Version;
 use siphasher::sip::SipHasher24;
 
 use crate::{
-    cargo::{CanonicalUrl, GitReference, InstallInfo, PackageId},
+    cargo::{CanonicalUrl, GitReference, InstallInfo, PackageId, SourceKind},
     colors, common,
-    models::{GitChanges, GitInfo, GitTarget, UpdateInfo},
-    table::GitTable,
+    install::{self, InstallJob, InstallOptions},
+    models::{GitChanges, GitInfo, GitTarget, InstallResult, UpdateInfo},
+    table::{GitTable, PinnedTable},
 };
 
+/// Check for new commits on the branch that the package was installed from. Packages installed
+/// from a tag are only moved to newer tags if `follow_tags` is enabled.
+///
+/// Packages pinned to a specific revision are compared against the remote default branch, but
+/// it's up to the caller to decide whether to move them.
 pub(crate) fn check_update(
     package: &PackageId,
     git_ref: &GitReference,
     git: bool,
+    follow_tags: bool,
+    pre: bool,
 ) -> Result<Option<GitInfo>> {
     if !git {
         return Ok(None);
@@ -38,29 +47,47 @@ pub(crate) fn check_update(
 
     let mut remote = repo.remote_at(package.source_id.url.as_str())?;
 
-    let (refspec, target, r#type, git_target) = match git_ref {
-        GitReference::Tag(_) => return Ok(None), // don't touch tags (yet)
+    let refspec = match git_ref {
+        GitReference::Tag(_) if !follow_tags => return Ok(None),
+        GitReference::Tag(_) => "+refs/tags/*:refs/tags/*".to_owned(),
+        GitReference::Branch(b) => format!("+refs/heads/{b}:refs/remotes/origin/{b}"),
+        GitReference::Rev(_) | GitReference::DefaultBranch => {
+            "+HEAD:refs/remotes/origin/HEAD".to_owned()
+        }
+    };
+
+    remote.replace_refspecs([refspec.as_str()], Direction::Fetch)?;
+    remote
+        .connect(Direction::Fetch)?
+        .prepare_fetch(gix::progress::Discard, Default::default())?
+        .receive(gix::progress::Discard, &gix::interrupt::IS_INTERRUPTED)?;
+
+    let (target, r#type, git_target) = match git_ref {
+        GitReference::Tag(tag) => match newest_tag(&repo, tag, pre)? {
+            Some(new_tag) => (
+                format!("refs/tags/{new_tag}"),
+                format!("tag {tag} ➞ {new_tag}"),
+                GitTarget::Tag(new_tag),
+            ),
+            None => return Ok(None),
+        },
         GitReference::Branch(b) => (
-            format!("+refs/heads/{b}:refs/remotes/origin/{b}"),
             format!("refs/remotes/origin/{b}"),
             format!("branch {b}"),
             GitTarget::Branch(b.clone()),
         ),
-        GitReference::Rev(_) => return Ok(None), // don't move pinned revs
+        GitReference::Rev(r) => (
+            "refs/remotes/origin/HEAD".to_owned(),
+            format!("rev {r:.7} ➞ HEAD"),
+            GitTarget::Default,
+        ),
         GitReference::DefaultBranch => (
-            "+HEAD:refs/remotes/origin/HEAD".to_owned(),
             "refs/remotes/origin/HEAD".to_owned(),
             "HEAD".to_owned(),
             GitTarget::Default,
         ),
     };
 
-    remote.replace_refspecs([refspec.as_str()], Direction::Fetch)?;
-    remote
-        .connect(Direction::Fetch)?
-        .prepare_fetch(gix::progress::Discard, Default::default())?
-        .receive(gix::progress::Discard, &gix::interrupt::IS_INTERRUPTED)?;
-
     let current = repo.find_object(commit_id)?.try_into_commit()?;
     let latest = repo
         .find_reference(&target)?
@@ -79,7 +106,11 @@ pub(crate) fn check_update(
     }))
 }
 
-pub(crate) fn print_updates(updates: &BTreeMap<PackageId, UpdateInfo<GitInfo>>, enabled: bool) {
+pub(crate) fn print_updates(
+    updates: &BTreeMap<PackageId, UpdateInfo<GitInfo>>,
+    host: &str,
+    enabled: bool,
+) {
     if !enabled {
         println!(
             "{} crate updates {}",
@@ -91,101 +122,138 @@ pub(crate) fn print_updates(updates: &BTreeMap<PackageId, UpdateInfo<GitInfo>>,
     } else {
         let table = updates
             .iter()
-            .map(|(pkg, info)| (pkg.name.as_str(), &info.extra))
+            .map(|(pkg, info)| {
+                (
+                    pkg.name.as_str(),
+                    &info.extra,
+                    info.install_info.cross_target(host),
+                )
+            })
             .collect::<GitTable>();
 
         println!("\n{table}\n");
     }
 }
 
+/// Print the packages pinned to a specific revision, that are behind the remote default branch.
+/// These are only informational, unless explicitly unpinned.
+pub(crate) fn print_pinned(pinned: &BTreeMap<PackageId, GitInfo>) {
+    if pinned.is_empty() {
+        return;
+    }
+
+    let table = pinned
+        .iter()
+        .map(|(pkg, info)| (pkg.name.as_str(), info))
+        .collect::<PinnedTable>();
+
+    println!("\n{table}");
+    println!(
+        "{}\n",
+        colors::dimmed("use `--unpin <CRATE>` to install them from the default branch instead")
+    );
+}
+
 pub(crate) fn install_updates(
     updates: impl ExactSizeIterator<Item = (PackageId, UpdateInfo<GitInfo>)>,
-    quiet: bool,
-) {
+    options: &InstallOptions,
+) -> Vec<InstallResult> {
     let count = updates.len();
     if count == 0 {
-        return;
+        return Vec::new();
     }
 
-    println!(
+    options.message(format_args!(
         "start installing {} {} updates\n",
         colors::blue(count).bold(),
         colors::green("git").bold()
-    );
-
-    for (i, (pkg, info)) in updates.enumerate() {
-        println!(
-            "{} updating {} from {} to {}",
-            colors::bold(format_args!("[{}/{}]", i + 1, count)),
-            colors::green(&pkg.name).bold(),
-            colors::blue(info.extra.old_commit).bold(),
-            colors::blue(info.extra.new_commit).bold()
-        );
+    ));
+
+    let jobs = updates
+        .map(|(pkg, info)| InstallJob {
+            change: format!(
+                "{} ➞ {}",
+                info.extra.old_commit.to_hex_with_len(7),
+                info.extra.new_commit.to_hex_with_len(7)
+            ),
+            from: info.extra.old_commit.to_string(),
+            to: Some(info.extra.new_commit.to_string()),
+            message: format!(
+                "updating {} from {} to {}",
+                colors::green(&pkg.name).bold(),
+                colors::blue(info.extra.old_commit).bold(),
+                colors::blue(info.extra.new_commit).bold()
+            ),
+            cmd: Ok(cargo_install(
+                &pkg,
+                &info.extra.target,
+                &info.install_info,
+                &options.host,
+            )),
+            package: pkg,
+            info: info.install_info,
+        })
+        .collect();
 
-        if let Err(e) = cargo_install(
-            &pkg.name,
-            pkg.source_id.url.as_str(),
-            &info.extra.target,
-            &info.install_info,
-            quiet,
-        ) {
-            println!(
-                "\ninstalling {} {}:\n{e}",
-                colors::green(pkg.name).bold(),
-                colors::red("failed").bold()
-            )
-        }
-    }
+    install::run(jobs, "installing", options)
 }
 
 fn cargo_install(
-    name: &str,
-    git_url: &str,
+    package: &PackageId,
     git_ref: &GitTarget,
     info: &InstallInfo,
-    quiet: bool,
-) -> Result<()> {
+    host: &str,
+) -> Command {
     let mut cmd = Command::new("cargo");
-    cmd.args(["install", name]);
-    cmd.args(["--git", git_url]);
+    cmd.args(["install", &package.name]);
+    cmd.args(["--git", package.source_id.url.as_str()]);
 
     match git_ref {
         GitTarget::Default => {} // This is the default, so nothing to do
         GitTarget::Branch(b) => {
             cmd.args(["--branch", b]);
         }
+        GitTarget::Tag(t) => {
+            cmd.args(["--tag", t]);
+        }
+    }
+
+    // Cargo considers a different tag or revision a different source, and refuses to replace the
+    // binaries of the old one otherwise.
+    let same_ref = matches!(
+        (&package.source_id.kind, git_ref),
+        (
+            SourceKind::Git(GitReference::DefaultBranch),
+            GitTarget::Default
+        ) | (
+            SourceKind::Git(GitReference::Branch(_)),
+            GitTarget::Branch(_)
+        )
+    );
+    if !same_ref {
+        cmd.arg("--force");
     }
 
-    common::apply_cmd_args(&mut cmd, info);
-    common::run_cmd(cmd, name, quiet)
+    common::apply_cmd_args(&mut cmd, info, host);
+    cmd
 }
 
 fn git_changes<'r>(repo: &'r Repository, old: &Commit<'r>, new: &Commit<'r>) -> Result<GitChanges> {
-    use gix::{
-        diff::blob::pipeline::{Mode, WorktreeRoots},
-        object::tree::diff::Action,
-    };
+    use gix::{object::tree::diff::Action, traverse::commit::topo::Builder};
 
-    let commits = new
-        .ancestors()
-        .all()?
+    // Only count the commits that aren't reachable from the old one, like `git rev-list old..new`,
+    // so a new tag that isn't a descendant of the old one doesn't count the entire history.
+    let commits = Builder::from_iters(&repo.objects, [new.id], Some([old.id]))
+        .build()?
         .map_while(Result::ok)
-        .take_while(|commit| commit.id != new.id)
         .count();
 
     if commits == 0 {
         return Ok(GitChanges::default());
     }
 
-    let mut diff_cache = repo
-        .diff_resource_cache(
-            Mode::default(),
-            WorktreeRoots {
-                old_root: None,
-                new_root: None,
-            },
-        )
-        .context("diff resource")?;
+    let mut diff_cache = resource_cache(repo, new).context("diff resource")?;
+    let mut rewrite_cache = resource_cache(repo, new).context("diff resource")?;
     let mut files_changed = 0;
     let mut insertions = 0;
     let mut deletions = 0;
@@ -194,7 +262,7 @@ fn git_changes<'r>(repo: &'r Repository, old: &Commit<'r>, new: &Commit<'r>) ->
         .context("tree")?
         .changes()
         .context("changes")?
-        .for_each_to_obtain_tree(&new.tree()?, |change| {
+        .for_each_to_obtain_tree_with_cache(&new.tree()?, &mut rewrite_cache, |change| {
             files_changed += 1;
 
             if let Some(counts) = change.diff(&mut diff_cache)?.line_counts()? {
@@ -214,11 +282,76 @@ fn git_changes<'r>(repo: &'r Repository, old: &Commit<'r>, new: &Commit<'r>) ->
     })
 }
 
+/// Create the cache for diffing blobs. The cargo git db is bare and its HEAD usually unborn, so the
+/// attributes are taken from the given commit, instead of the index that
+/// [`Repository::diff_resource_cache`] would load from HEAD.
+fn resource_cache(repo: &Repository, commit: &Commit<'_>) -> Result<gix::diff::blob::Platform> {
+    use gix::{
+        diff::blob::pipeline::{Mode, WorktreeRoots},
+        worktree::stack::state::attributes::Source,
+    };
+
+    let index = repo.index_from_tree(&commit.tree_id()?)?;
+    let attributes = repo.attributes_only(&index, Source::IdMapping)?.detach();
+
+    gix::diff::resource_cache(
+        repo,
+        Mode::default(),
+        attributes,
+        WorktreeRoots {
+            old_root: None,
+            new_root: None,
+        },
+    )
+    .map_err(Into::into)
+}
+
+/// Find the tag with the highest version, that is greater than the version of the given tag. Only
+/// tags with the same prefix are considered, like `foo-v1.2.3` for crates from a workspace.
+fn newest_tag(repo: &Repository, current: &str, pre: bool) -> Result<Option<String>> {
+    let Some((prefix, current)) = parse_tag(current) else {
+        return Ok(None);
+    };
+
+    let mut newest = None::<(Version, String)>;
+
+    for reference in repo.references()?.tags()? {
+        let reference = reference.map_err(|e| anyhow!(e))?;
+        let name = reference.name().shorten().to_string();
+
+        let Some((p, version)) = parse_tag(&name) else {
+            continue;
+        };
+
+        if p != prefix || version <= current || (!version.pre.is_empty() && !pre) {
+            continue;
+        }
+
+        if newest.as_ref().is_none_or(|(v, _)| version > *v) {
+            newest = Some((version, name));
+        }
+    }
+
+    Ok(newest.map(|(_, name)| name))
+}
+
+/// Split a tag name into its prefix and the version, which may be preceded by a `v`. The prefix
+/// must be separated by a `-`, `_` or `/` from the version.
+fn parse_tag(tag: &str) -> Option<(&str, Version)> {
+    tag.char_indices()
+        .filter(|&(i, _)| i == 0 || tag[..i].ends_with(['-', '_', '/']))
+        .find_map(|(i, _)| {
+            let rest = &tag[i..];
+            let version = Version::parse(rest.strip_prefix('v').unwrap_or(rest)).ok()?;
+            Some((&tag[..i], version))
+        })
+}
+
 fn get_git_repo_path(canonical_url: &CanonicalUrl) -> Result<PathBuf> {
     let ident = canonical_url
         .0
         .path_segments()
-        .and_then(|s| s.last())
+        .and_then(|mut s| s.next_back())
         .unwrap_or("");
 
     let ident = if ident.is_empty() { "_empty" } else { ident };
@@ -241,3 +374,28 @@ fn open_or_init_repo(path: &Path) -> Result<Repository> {
         gix::init_bare(path).map_err(Into::into)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tag_versions() {
+        assert_eq!(Some(("", Version::new(1, 2, 3))), parse_tag("v1.2.3"));
+        assert_eq!(Some(("", Version::new(1, 2, 3))), parse_tag("1.2.3"));
+        assert_eq!(
+            Some(("foo-", Version::new(0, 1, 0))),
+            parse_tag("foo-v0.1.0")
+        );
+        assert_eq!(
+            Some(("foo/", Version::new(0, 1, 0))),
+            parse_tag("foo/0.1.0")
+        );
+        assert_eq!(
+            Some(("", Version::parse("2.0.0-rc.1").unwrap())),
+            parse_tag("v2.0.0-rc.1")
+        );
+        assert_eq!(None, parse_tag("release"));
+        assert_eq!(None, parse_tag("v1.2"));
+    }
+}
@@ -0,0 +1,250 @@
+//! Log of all installation attempts, kept in `$CARGO_HOME/bup/history.jsonl` with one JSON object
+//! per line, that can be inspected with the `history` command.
+
+use std::{
+    fs::{self, OpenOptions},
+    io::{self, ErrorKind, Write},
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+
+use anstream::println;
+use anyhow::{Context, Result};
+use glob::Pattern;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    cargo::{PackageId, SourceKind},
+    cli::{Format, HistoryArgs},
+    colors,
+    models::{InstallOutcome, InstallResult},
+    table::HistoryTable,
+};
+
+/// Single installation attempt.
+#[derive(Deserialize, Serialize)]
+pub struct Entry {
+    /// Point in time the installation finished, in seconds since the Unix epoch.
+    pub timestamp: u64,
+    pub name: String,
+    pub source: String,
+    pub url: String,
+    /// Version or commit that was installed before.
+    pub from: String,
+    /// Version or commit that was installed, if known upfront.
+    pub to: Option<String>,
+    /// Short description of the change, like the old and new version.
+    pub change: String,
+    pub outcome: InstallOutcome,
+    /// Time it took to install the update, in seconds.
+    pub duration: f64,
+    /// Version of the Rust toolchain that was used.
+    pub rustc: String,
+    pub error: Option<String>,
+}
+
+impl Entry {
+    pub fn new(from: String, to: Option<String>, result: &InstallResult, rustc: &str) -> Self {
+        Self {
+            timestamp: SystemTime::now()
+                .duration_since(SystemTime::UNIX_EPOCH)
+                .map_or(0, |d| d.as_secs()),
+            name: result.package.name.clone(),
+            source: source_name(&result.package).to_owned(),
+            url: result.package.source_id.url.to_string(),
+            from,
+            to,
+            change: result.change.clone(),
+            outcome: result.outcome,
+            duration: result.duration.as_secs_f64(),
+            rustc: rustc.to_owned(),
+            error: result.error.clone(),
+        }
+    }
+
+    pub fn time(&self) -> SystemTime {
+        SystemTime::UNIX_EPOCH + Duration::from_secs(self.timestamp)
+    }
+
+    /// Append the entry to the history file, creating it if necessary.
+    pub fn append(&self, path: &Path) -> Result<()> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        let mut line = serde_json::to_vec(self)?;
+        line.push(b'\n');
+
+        // Written at once, so concurrent installations don't interleave their entries.
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)?
+            .write_all(&line)
+            .with_context(|| format!("failed writing to `{}`", path.display()))
+    }
+}
+
+/// Name of the kind of source that the package was installed from.
+pub fn source_name(package: &PackageId) -> &'static str {
+    match package.source_id.kind {
+        SourceKind::Registry => "registry",
+        SourceKind::Git(_) => "git",
+        SourceKind::Path => "path",
+    }
+}
+
+/// Location of the history file.
+pub fn file() -> Result<PathBuf> {
+    Ok(home::cargo_home()?.join("bup/history.jsonl"))
+}
+
+/// Print the entries of the history file that match the filters, oldest first.
+pub fn show(args: &HistoryArgs) -> Result<()> {
+    let entries = read(&file()?, args, SystemTime::now())?;
+
+    match args.format {
+        Format::Human if entries.is_empty() => {
+            println!("no {} entries", colors::green("history"));
+        }
+        Format::Human => {
+            println!("\n{}", entries.iter().collect::<HistoryTable>());
+        }
+        Format::Json => {
+            let mut stdout = io::stdout().lock();
+            serde_json::to_writer_pretty(&mut stdout, &entries)?;
+            writeln!(stdout)?;
+        }
+        Format::Ndjson => {
+            let mut stdout = io::stdout().lock();
+            for entry in &entries {
+                serde_json::to_writer(&mut stdout, entry)?;
+                writeln!(stdout)?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Read the entries of the history file that match the filters, keeping only the most recent ones
+/// up to the limit. The time span of `--since` is counted back from `now`.
+fn read(path: &Path, args: &HistoryArgs, now: SystemTime) -> Result<Vec<Entry>> {
+    let content = match fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == ErrorKind::NotFound => String::new(),
+        Err(e) => return Err(e).with_context(|| format!("failed reading `{}`", path.display())),
+    };
+
+    let patterns = args
+        .crates
+        .iter()
+        .map(|name| Pattern::new(name).with_context(|| format!("invalid crate pattern `{name}`")))
+        .collect::<Result<Vec<_>>>()?;
+    let since = args
+        .since
+        .and_then(|days| now.checked_sub(Duration::from_secs(days.saturating_mul(86_400))));
+
+    let mut entries = Vec::new();
+    for (i, line) in content.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let entry = serde_json::from_str::<Entry>(line)
+            .with_context(|| format!("failed parsing line {} of `{}`", i + 1, path.display()))?;
+
+        if (patterns.is_empty() || patterns.iter().any(|p| p.matches(&entry.name)))
+            && (!args.failed || entry.outcome == InstallOutcome::Failed)
+            && since.is_none_or(|since| entry.time() >= since)
+        {
+            entries.push(entry);
+        }
+    }
+
+    entries.drain(..entries.len().saturating_sub(args.limit));
+    Ok(entries)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HISTORY: &str = r#"{"timestamp":1000000,"name":"foo","source":"registry","url":"https://github.com/rust-lang/crates.io-index","from":"1.0.0","to":"1.1.0","change":"1.0.0 ➞ 1.1.0","outcome":"updated","duration":12.5,"rustc":"1.78.0","error":null}
+{"timestamp":2000000,"name":"foo-cli","source":"git","url":"https://github.com/foo/foo","from":"265df66","to":null,"change":"2 new commits","outcome":"failed","duration":3.0,"rustc":"1.78.0","error":"cargo failed with exit status: 101"}
+
+{"timestamp":3000000,"name":"bar","source":"path","url":"file:///src/bar","from":"0.1.0","to":null,"change":"3 files changed","outcome":"skipped","duration":0.0,"rustc":"1.79.0","error":"directory `/src/bar` doesn't exist anymore"}
+{"timestamp":4000000,"name":"foo","source":"registry","url":"https://github.com/rust-lang/crates.io-index","from":"1.1.0","to":"1.2.0","change":"1.1.0 ➞ 1.2.0","outcome":"failed","duration":8.0,"rustc":"1.79.0","error":"cargo failed with exit status: 101"}
+"#;
+
+    fn args(crates: &[&str], failed: bool, since: Option<u64>, limit: usize) -> HistoryArgs {
+        HistoryArgs {
+            crates: crates.iter().map(|name| (*name).to_owned()).collect(),
+            failed,
+            since,
+            limit,
+            format: Format::Human,
+        }
+    }
+
+    #[test]
+    fn filter_entries() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("history.jsonl");
+        fs::write(&path, HISTORY).unwrap();
+
+        let now = SystemTime::UNIX_EPOCH + Duration::from_secs(4_000_000);
+        let read = |args| {
+            read(&path, &args, now)
+                .unwrap()
+                .into_iter()
+                .map(|entry| entry.timestamp)
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            vec![1_000_000, 2_000_000, 3_000_000, 4_000_000],
+            read(args(&[], false, None, 20))
+        );
+        assert_eq!(
+            vec![1_000_000, 2_000_000, 4_000_000],
+            read(args(&["foo*"], false, None, 20))
+        );
+        assert_eq!(
+            vec![1_000_000, 3_000_000, 4_000_000],
+            read(args(&["foo", "bar"], false, None, 20))
+        );
+        assert_eq!(vec![2_000_000, 4_000_000], read(args(&[], true, None, 20)));
+        // Twelve days before the last entry reach back to the previous one.
+        assert_eq!(
+            vec![3_000_000, 4_000_000],
+            read(args(&[], false, Some(12), 20))
+        );
+        // The limit keeps the most recent entries.
+        assert_eq!(vec![3_000_000, 4_000_000], read(args(&[], false, None, 2)));
+        assert_eq!(vec![4_000_000], read(args(&["foo"], true, None, 1)));
+
+        fs::remove_file(&path).unwrap();
+        assert!(read(args(&[], false, None, 20)).is_empty());
+    }
+
+    #[test]
+    fn entry_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("history.jsonl");
+
+        for line in HISTORY.lines().filter(|line| !line.is_empty()) {
+            let entry = serde_json::from_str::<Entry>(line).unwrap();
+            assert_eq!(line, serde_json::to_string(&entry).unwrap());
+            entry.append(&path).unwrap();
+        }
+
+        let entries = read(&path, &args(&[], false, None, 20), SystemTime::now()).unwrap();
+        assert_eq!(4, entries.len());
+        assert_eq!(
+            SystemTime::UNIX_EPOCH + Duration::from_secs(3_000_000),
+            entries[2].time()
+        );
+        assert!(entries[2].outcome == InstallOutcome::Skipped);
+    }
+}
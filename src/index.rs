@@ -0,0 +1,290 @@
+//! Access to the indexes of crate registries, either through a local clone of a Git based index
+//! or by fetching single entries with the sparse HTTP protocol.
+
+use std::{
+    cell::RefCell,
+    collections::{btree_map::Entry, BTreeMap},
+    path::Path,
+};
+
+use anyhow::{Context, Result};
+use crates_index::{Crate, GitIndex, SparseIndex};
+use curl::easy::{Easy, List};
+use rayon::iter::{IntoParallelRefIterator, ParallelIterator};
+use thread_local::ThreadLocal;
+
+use crate::cargo::{Protocol, SourceId, CRATES_IO_HTTP_INDEX, CRATES_IO_INDEX};
+
+/// Determine the location of the index for the given registry source. Packages from the main
+/// crates.io registry are resolved to the given protocol, regardless of the protocol that was
+/// used when they were installed.
+pub fn location(source_id: &SourceId, protocol: Protocol) -> &str {
+    if source_id.is_crates_io() {
+        match protocol {
+            Protocol::Git => CRATES_IO_INDEX,
+            Protocol::Sparse => CRATES_IO_HTTP_INDEX,
+        }
+    } else {
+        source_id.url.as_str()
+    }
+}
+
+/// Index of a single registry, using either of the protocols that cargo supports.
+pub enum RegistryIndex {
+    Git(Box<GitIndex>),
+    Sparse(SparseIndex),
+}
+
+impl RegistryIndex {
+    /// Open the index at the given [`location`], using the same directory on disk that cargo uses
+    /// for it. Git based indexes are cloned if they don't exist yet.
+    pub fn open(url: &str) -> Result<Self> {
+        if url == CRATES_IO_INDEX {
+            return Ok(Self::Git(Box::new(GitIndex::new_cargo_default()?)));
+        }
+
+        Self::open_in(&home::cargo_home()?, url)
+    }
+
+    /// Open the index at the given [`location`], within the given cargo home directory.
+    fn open_in(cargo_home: &Path, url: &str) -> Result<Self> {
+        let index = if url.starts_with("sparse+") {
+            Self::Sparse(SparseIndex::with_path(cargo_home, url)?)
+        } else {
+            let (path, url) = crates_index::local_path_and_canonical_url(
+                &format!("registry+{url}"),
+                Some(cargo_home),
+            )?;
+            Self::Git(Box::new(GitIndex::with_path(path, url)?))
+        };
+
+        Ok(index)
+    }
+
+    /// Bring the local copy of the index up to date with the remote. Git based indexes are
+    /// fetched as a whole, whereas sparse indexes only fetch the index configuration and the
+    /// entries of the given crates, all in parallel.
+    pub fn update(&mut self, names: &[&str]) -> Result<()> {
+        match self {
+            Self::Git(index) => index.update()?,
+            Self::Sparse(index) => {
+                let index = &*index;
+                let (config, entries) = rayon::join(
+                    || {
+                        let response = fetch(index.make_config_request()?)?;
+                        index
+                            .parse_config_response(response, true)
+                            .context("failed fetching index configuration")?;
+                        anyhow::Ok(())
+                    },
+                    || {
+                        names.par_iter().try_for_each(|name| {
+                            let response = fetch(index.make_cache_request(name)?)?;
+                            index
+                                .parse_cache_response(name, response, true)
+                                .with_context(|| {
+                                    format!("failed fetching index entry for `{name}`")
+                                })?;
+                            anyhow::Ok(())
+                        })
+                    },
+                );
+
+                config?;
+                entries?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Read the information about a single crate from the local copy of the index.
+    pub fn crate_(&self, name: &str) -> Result<Crate> {
+        match self {
+            Self::Git(index) => index.crate_(name).context("failed finding package"),
+            Self::Sparse(index) => index
+                .crate_from_cache(name)
+                .context("failed finding package"),
+        }
+    }
+}
+
+/// Per-thread cache of opened registry indexes, as the Git based index can't be shared between
+/// threads.
+pub struct Indexes {
+    /// Protocol to access the crates.io registry with.
+    protocol: Protocol,
+    cache: ThreadLocal<RefCell<BTreeMap<String, RegistryIndex>>>,
+}
+
+impl Indexes {
+    pub fn new(protocol: Protocol) -> Self {
+        Self {
+            protocol,
+            cache: ThreadLocal::new(),
+        }
+    }
+
+    /// Run the given function with the index of the registry that the source points to, opening
+    /// it first if this thread didn't access it yet.
+    pub fn with<T>(
+        &self,
+        source_id: &SourceId,
+        f: impl FnOnce(&RegistryIndex) -> Result<T>,
+    ) -> Result<T> {
+        let url = location(source_id, self.protocol);
+        let mut indexes = self.cache.get_or_default().borrow_mut();
+        let index = match indexes.entry(url.to_owned()) {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => entry.insert(RegistryIndex::open(url)?),
+        };
+
+        f(index)
+    }
+}
+
+/// Execute a single HTTP request, as created by the [`SparseIndex`], and turn the outcome into a
+/// response that can be passed back to it.
+fn fetch(request: http::request::Builder) -> Result<http::Response<Vec<u8>>> {
+    let request = request.body(())?;
+
+    let mut easy = Easy::new();
+    easy.url(&request.uri().to_string())?;
+    easy.follow_location(true)?;
+
+    let mut headers = List::new();
+    for (name, value) in request.headers() {
+        if name == http::header::ACCEPT_ENCODING {
+            // Let curl negotiate the encoding, so it transparently decompresses the response.
+            easy.accept_encoding(value.to_str()?)?;
+        } else {
+            headers.append(&format!("{name}: {}", value.to_str()?))?;
+        }
+    }
+    easy.http_headers(headers)?;
+
+    let mut response_headers = Vec::new();
+    let mut body = Vec::new();
+    {
+        let mut transfer = easy.transfer();
+        transfer.header_function(|line| {
+            if let Some((name, value)) = std::str::from_utf8(line)
+                .ok()
+                .and_then(|line| line.split_once(':'))
+            {
+                response_headers.push((name.trim().to_owned(), value.trim().to_owned()));
+            }
+            true
+        })?;
+        transfer.write_function(|data| {
+            body.extend_from_slice(data);
+            Ok(data.len())
+        })?;
+        transfer.perform()?;
+    }
+
+    let mut response = http::Response::builder().status(u16::try_from(easy.response_code()?)?);
+    for (name, value) in response_headers {
+        response = response.header(name, value);
+    }
+
+    response.body(body).map_err(Into::into)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        fs,
+        io::{BufRead, BufReader, Write},
+        net::TcpListener,
+        process::Command,
+        thread,
+    };
+
+    use super::*;
+
+    const ENTRIES: &str = concat!(
+        r#"{"name":"foo","vers":"1.0.0","deps":[],"cksum":"0000000000000000000000000000000000000000000000000000000000000000","features":{},"yanked":false}"#,
+        "\n",
+        r#"{"name":"foo","vers":"1.1.0","deps":[],"cksum":"0000000000000000000000000000000000000000000000000000000000000000","features":{},"yanked":false}"#,
+        "\n",
+    );
+
+    fn newest(index: &RegistryIndex) -> String {
+        let krate = index.crate_("foo").unwrap();
+        krate.highest_version().version().to_owned()
+    }
+
+    #[test]
+    fn local_git_index() {
+        let remote = tempfile::tempdir().unwrap();
+        fs::create_dir_all(remote.path().join("3/f")).unwrap();
+        fs::write(remote.path().join("3/f/foo"), ENTRIES).unwrap();
+        fs::write(remote.path().join("config.json"), r#"{"dl":"file:///dl"}"#).unwrap();
+
+        let git = |args: &[&str]| {
+            let status = Command::new("git")
+                .args(["-c", "user.name=test", "-c", "user.email=test@localhost"])
+                .args(args)
+                .current_dir(remote.path())
+                .status()
+                .unwrap();
+            assert!(status.success());
+        };
+        git(&["init", "--quiet"]);
+        git(&["add", "."]);
+        git(&["commit", "--quiet", "-m", "index"]);
+
+        let cargo_home = tempfile::tempdir().unwrap();
+        let url = url::Url::from_directory_path(remote.path()).unwrap();
+        let mut index = RegistryIndex::open_in(cargo_home.path(), url.as_str()).unwrap();
+        index.update(&["foo"]).unwrap();
+
+        assert!(matches!(index, RegistryIndex::Git(_)));
+        assert_eq!("1.1.0", newest(&index));
+    }
+
+    #[test]
+    fn local_sparse_index() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("sparse+http://{}/", listener.local_addr().unwrap());
+
+        // Minimal stand-in for a sparse registry, answering each request with the file at the
+        // requested path, and closing the connection afterwards.
+        let config = format!(r#"{{"dl":"{}dl"}}"#, &url["sparse+".len()..]);
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let mut stream = stream.unwrap();
+                let mut reader = BufReader::new(&stream);
+
+                let mut request = String::new();
+                reader.read_line(&mut request).unwrap();
+                loop {
+                    let mut line = String::new();
+                    if reader.read_line(&mut line).unwrap() == 0 || line.trim().is_empty() {
+                        break;
+                    }
+                }
+
+                let (status, body) = match request.split_whitespace().nth(1) {
+                    Some("/config.json") => ("200 OK", config.as_str()),
+                    Some("/3/f/foo") => ("200 OK", ENTRIES),
+                    _ => ("404 Not Found", ""),
+                };
+                write!(
+                    stream,
+                    "HTTP/1.1 {status}\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{body}",
+                    body.len()
+                )
+                .unwrap();
+            }
+        });
+
+        let cargo_home = tempfile::tempdir().unwrap();
+        let mut index = RegistryIndex::open_in(cargo_home.path(), &url).unwrap();
+        index.update(&["foo"]).unwrap();
+
+        assert!(matches!(index, RegistryIndex::Sparse(_)));
+        assert_eq!("1.1.0", newest(&index));
+    }
+}
@@ -0,0 +1,417 @@
+//! Execution of the prepared `cargo install` commands, either one after another or several at the
+//! same time.
+
+use std::{
+    fmt, fs,
+    io::{self, IsTerminal, Write},
+    path::PathBuf,
+    process::Command,
+    sync::Mutex,
+};
+
+use anstream::{eprintln, println};
+use anyhow::{Context, Result};
+use rayon::{
+    iter::{IntoParallelIterator, ParallelIterator},
+    ThreadPoolBuilder,
+};
+
+use crate::{
+    backup::{self, Backup},
+    cargo::{InstallInfo, PackageId},
+    colors,
+    common::{self, CmdOutput, Output},
+    history,
+    models::{InstallOutcome, InstallResult},
+    table::display_duration,
+};
+
+/// Options that apply to all installations.
+pub struct InstallOptions {
+    /// Hide cargo's build output.
+    pub quiet: bool,
+    /// Maximum number of installations to run at the same time.
+    pub jobs: usize,
+    /// Target directory that is shared between all builds, if any.
+    pub target_dir: Option<PathBuf>,
+    /// Further arguments for `cargo install`.
+    pub cargo: CargoArgs,
+    /// Directory to write the build output of concurrent or quiet installations to.
+    pub log_dir: PathBuf,
+    /// Directory to keep the backups of all crates in.
+    pub backup_dir: PathBuf,
+    /// Number of backups to keep per crate. Nothing is backed up, if zero.
+    pub keep_backups: usize,
+    /// Arguments to run the updated binaries with, to check that they start up. They're rolled
+    /// back if that fails.
+    pub smoke_test: Option<Vec<String>>,
+    /// File to record all installation attempts in.
+    pub history: PathBuf,
+    /// Version of the active Rust toolchain.
+    pub rustc: String,
+    /// Target triple of the active Rust toolchain.
+    pub host: String,
+    /// Write progress messages and cargo's output to the standard error stream, as the standard
+    /// output is reserved for a machine readable report.
+    pub stderr: bool,
+}
+
+impl InstallOptions {
+    fn concurrent(&self) -> bool {
+        self.jobs > 1
+    }
+
+    /// Print a progress message about the installations.
+    pub fn message(&self, msg: fmt::Arguments<'_>) {
+        print_message(msg, self.stderr);
+    }
+}
+
+/// Arguments that are passed on to every `cargo install` invocation.
+#[derive(Default)]
+pub struct CargoArgs {
+    pub locked: bool,
+    pub offline: bool,
+    pub force: bool,
+    pub build_jobs: Option<u32>,
+    pub config: Vec<String>,
+    /// Any further arguments, that are passed on verbatim.
+    pub args: Vec<String>,
+}
+
+impl CargoArgs {
+    fn apply(&self, cmd: &mut Command) {
+        if self.locked {
+            cmd.arg("--locked");
+        }
+
+        if self.offline {
+            cmd.arg("--offline");
+        }
+
+        // Some installations need to be forced anyway, and cargo rejects repeated flags.
+        if self.force && !cmd.get_args().any(|arg| arg == "--force") {
+            cmd.arg("--force");
+        }
+
+        if let Some(jobs) = self.build_jobs {
+            cmd.args(["--jobs", &jobs.to_string()]);
+        }
+
+        for config in &self.config {
+            cmd.args(["--config", config]);
+        }
+
+        cmd.args(&self.args);
+    }
+}
+
+/// Single prepared installation.
+pub struct InstallJob {
+    pub package: PackageId,
+    /// Settings of the currently installed version.
+    pub info: InstallInfo,
+    /// Short description of the change, like the old and new version.
+    pub change: String,
+    /// Version or commit that is currently installed.
+    pub from: String,
+    /// Version or commit that is going to be installed, if known upfront.
+    pub to: Option<String>,
+    /// Progress message that is shown when the installation starts.
+    pub message: String,
+    /// The cargo command to run, or the reason for skipping the installation.
+    pub cmd: Result<Command>,
+}
+
+/// Run all the installations, returning their results in the same order.
+///
+/// Concurrent installations write cargo's output to a log file per crate, instead of interleaving
+/// it on the terminal. Cargo itself serializes the final copy into the installation root, but a
+/// shared target directory would block all other builds, so each crate gets its own sub-directory
+/// instead.
+pub fn run(jobs: Vec<InstallJob>, verb: &str, options: &InstallOptions) -> Vec<InstallResult> {
+    let progress = Progress::new(jobs.len(), options);
+
+    let run = |job: InstallJob| {
+        progress.start(&job);
+        let (from, to) = (job.from.clone(), job.to.clone());
+        let result = install(job, options);
+        progress.finish(&result, verb);
+
+        let entry = history::Entry::new(from, to, &result, &options.rustc);
+        if let Err(e) = entry.append(&options.history) {
+            eprintln!(
+                "{} recording history: {e:#}",
+                colors::yellow("failed").bold()
+            );
+        }
+        result
+    };
+
+    let results = if options.concurrent() {
+        match ThreadPoolBuilder::new().num_threads(options.jobs).build() {
+            Ok(pool) => pool.install(|| jobs.into_par_iter().map(run).collect()),
+            Err(_) => jobs.into_iter().map(run).collect(),
+        }
+    } else {
+        jobs.into_iter().map(run).collect()
+    };
+
+    progress.clear();
+    results
+}
+
+/// Install a single update, after backing up the current version. If the smoke test is enabled
+/// and the new version fails it, the backup is restored right away.
+///
+/// The backup is only kept if the update was installed, pruning older backups in turn. Otherwise,
+/// the backed up version is still in place and the backup is discarded again.
+fn install(job: InstallJob, options: &InstallOptions) -> InstallResult {
+    let cmd = match job.cmd {
+        Ok(cmd) => cmd,
+        Err(e) => return InstallResult::skipped(job.package, job.change, &e),
+    };
+
+    let backup = if options.keep_backups > 0 {
+        match Backup::create(&options.backup_dir, &job.package, &job.info) {
+            Ok(backup) => Some(backup),
+            Err(e) => {
+                let e = e.context("failed backing up the installed version");
+                return InstallResult::skipped(job.package, job.change, &e);
+            }
+        }
+    } else {
+        None
+    };
+
+    let output = run_cmd(cmd, &job.package, &job.info, options);
+    let mut result = InstallResult::new(job.package, job.change, output);
+
+    if let (InstallOutcome::Updated, Some(args)) = (result.outcome, &options.smoke_test) {
+        // Binaries built for another target usually can't run on this machine.
+        if let Some(target) = job.info.cross_target(&options.host) {
+            options.message(format_args!(
+                "skipping smoke test of {}, as it was built for {}",
+                colors::green(&result.package.name).bold(),
+                colors::cyan(target)
+            ));
+        } else if let Err(e) = backup::smoke_test(&job.info.root, &result.package.name, args) {
+            result.outcome = InstallOutcome::Failed;
+            result.error = Some(match backup {
+                Some(backup) => {
+                    let version = backup.version().to_owned();
+                    match backup.restore() {
+                        Ok(()) => format!("{e:#}, rolled back to {version}"),
+                        Err(restore) => format!("{e:#}, and rolling back failed: {restore:#}"),
+                    }
+                }
+                None => format!("{e:#}"),
+            });
+            return result;
+        }
+    }
+
+    if let Some(backup) = backup {
+        let cleanup = if result.outcome == InstallOutcome::Updated {
+            Backup::prune(
+                &options.backup_dir,
+                &result.package.name,
+                &job.info.root,
+                options.keep_backups,
+            )
+        } else {
+            backup.discard()
+        };
+
+        if let Err(e) = cleanup {
+            eprintln!(
+                "{} cleaning up backups: {e:#}",
+                colors::yellow("failed").bold()
+            );
+        }
+    }
+
+    result
+}
+
+fn run_cmd(
+    mut cmd: Command,
+    package: &PackageId,
+    info: &InstallInfo,
+    options: &InstallOptions,
+) -> Result<CmdOutput> {
+    // The same crate can be installed into several roots at the same time.
+    let key = format!("{}-{}", package.name, common::root_hash(&info.root));
+
+    if let Some(dir) = &options.target_dir {
+        cmd.arg("--target-dir");
+        if options.concurrent() {
+            cmd.arg(dir.join(&key));
+        } else {
+            cmd.arg(dir);
+        }
+    }
+
+    options.cargo.apply(&mut cmd);
+
+    if !options.concurrent() && !options.quiet {
+        let output = if options.stderr {
+            Output::Stderr
+        } else {
+            Output::Inherit
+        };
+        return common::run_cmd(cmd, output);
+    }
+
+    // The output is still kept in a log file, so it's at hand if the installation fails.
+
+    fs::create_dir_all(&options.log_dir).with_context(|| {
+        format!(
+            "failed creating log directory `{}`",
+            options.log_dir.display()
+        )
+    })?;
+
+    let log = options.log_dir.join(format!("{key}.log"));
+    common::run_cmd(cmd, Output::Log(&log))
+}
+
+/// Progress display for the installations. For concurrent installations, a status line with all
+/// currently building crates is kept at the bottom, if the output is a terminal.
+struct Progress {
+    total: usize,
+    concurrent: bool,
+    stderr: bool,
+    status_line: bool,
+    state: Mutex<ProgressState>,
+}
+
+#[derive(Default)]
+struct ProgressState {
+    started: usize,
+    running: Vec<String>,
+}
+
+impl Progress {
+    fn new(total: usize, options: &InstallOptions) -> Self {
+        let concurrent = options.concurrent();
+
+        Self {
+            total,
+            concurrent,
+            stderr: options.stderr,
+            status_line: concurrent && io::stderr().is_terminal(),
+            state: Mutex::default(),
+        }
+    }
+
+    fn start(&self, job: &InstallJob) {
+        let mut state = self.state.lock().unwrap();
+        state.started += 1;
+        state.running.push(job.package.name.clone());
+
+        self.clear_status();
+        print_message(
+            format_args!(
+                "{} {}",
+                colors::bold(format_args!("[{}/{}]", state.started, self.total)),
+                job.message
+            ),
+            self.stderr,
+        );
+        self.draw_status(&state);
+    }
+
+    fn finish(&self, result: &InstallResult, verb: &str) {
+        let mut state = self.state.lock().unwrap();
+        // The same crate can be installed into several roots at once, so only drop one of them.
+        if let Some(pos) = state
+            .running
+            .iter()
+            .position(|name| *name == result.package.name)
+        {
+            state.running.remove(pos);
+        }
+
+        self.clear_status();
+
+        let outcome = match result.outcome {
+            InstallOutcome::Updated => colors::green("succeeded").bold(),
+            InstallOutcome::Failed => colors::red("failed").bold(),
+            InstallOutcome::Skipped => colors::yellow("skipped").bold(),
+        };
+
+        if self.concurrent {
+            print_message(
+                format_args!(
+                    "{verb} {} {outcome} after {}",
+                    colors::green(&result.package.name).bold(),
+                    display_duration(result.duration)
+                ),
+                self.stderr,
+            );
+        }
+
+        if let Some(e) = &result.error {
+            if self.concurrent {
+                eprintln!("{e}");
+            } else {
+                eprintln!(
+                    "\n{verb} {} {outcome}:\n{e}",
+                    colors::green(&result.package.name).bold()
+                );
+            }
+
+            if let Some(log) = &result.log {
+                eprintln!("full build output in {}", colors::dimmed(log.display()));
+            }
+        }
+
+        self.draw_status(&state);
+    }
+
+    fn draw_status(&self, state: &ProgressState) {
+        if !self.status_line || state.running.is_empty() {
+            return;
+        }
+
+        // Keep the line short, so it doesn't wrap and can be cleared again.
+        const MAX_NAMES: usize = 4;
+
+        let mut stderr = anstream::stderr().lock();
+        write!(
+            stderr,
+            "{} {}",
+            colors::cyan("building").bold(),
+            state.running[..state.running.len().min(MAX_NAMES)].join(", ")
+        )
+        .ok();
+        if state.running.len() > MAX_NAMES {
+            write!(stderr, " and {} more", state.running.len() - MAX_NAMES).ok();
+        }
+        stderr.flush().ok();
+    }
+
+    fn clear_status(&self) {
+        if self.status_line {
+            let mut stderr = io::stderr().lock();
+            write!(stderr, "\r\x1b[2K").ok();
+            stderr.flush().ok();
+        }
+    }
+
+    fn clear(&self) {
+        if self.concurrent {
+            self.clear_status();
+            print_message(format_args!(""), self.stderr);
+        }
+    }
+}
+
+fn print_message(msg: fmt::Arguments<'_>, stderr: bool) {
+    if stderr {
+        eprintln!("{msg}");
+    } else {
+        println!("{msg}");
+    }
+}
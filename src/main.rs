@@ -1,85 +1,385 @@
-use std::{fmt, fs::File, io::Write, sync::Arc};
+use std::{
+    collections::{btree_map::Entry, BTreeMap, BTreeSet},
+    fmt,
+    io::Write,
+    path::PathBuf,
+    process::ExitCode,
+};
 
-use anstream::{print, println};
-use anyhow::Result;
-use cli::SelectArgs;
-use crates_index::GitIndex;
+use anstream::{eprint, eprintln, print, println};
+use anyhow::{bail, Result};
+use cli::{Format, InstallArgs, SelectArgs};
 use rayon::iter::{IntoParallelIterator, ParallelIterator};
-use thread_local::ThreadLocal;
+use rustc_version::VersionMeta;
+use semver::Version;
 
 use crate::{
-    cargo::{CrateListingV2, SourceKind},
+    cargo::{
+        Config, CrateListingV1, CrateListingV2, GitReference, ListingMismatch, PackageId, Protocol,
+        SourceKind,
+    },
     cli::Subcmd,
-    models::{UpdateInfo, Updates},
+    index::{Indexes, RegistryIndex},
+    install::{CargoArgs, InstallOptions},
+    models::{InstallOutcome, InstallResult, OutdatedInfo, UpdateInfo, UpdateLevel, Updates},
+    path::PathCheck,
+    report::Report,
+    settings::{CargoSettings, Settings},
+    table::SummaryTable,
 };
 
+mod backup;
 mod cargo;
 mod cli;
 mod colors;
 mod common;
+mod filter;
 mod git;
+mod history;
+mod index;
+mod install;
 mod models;
 mod path;
+mod rebuild;
 mod registry;
+mod report;
+mod select;
+mod settings;
 mod table;
 
-fn main() -> Result<()> {
+fn main() -> Result<ExitCode> {
     let cmd = cli::parse();
 
-    if let Some(Subcmd::Completions { shell }) = cmd.subcmd {
-        cli::completions(shell);
-        return Ok(());
+    match &cmd.subcmd {
+        Some(Subcmd::Completions { shell }) => {
+            cli::completions(*shell);
+            return Ok(ExitCode::SUCCESS);
+        }
+        Some(Subcmd::History(args)) => {
+            history::show(args)?;
+            return Ok(ExitCode::SUCCESS);
+        }
+        Some(Subcmd::Rollback { name }) => {
+            backup::rollback(name)?;
+            return Ok(ExitCode::SUCCESS);
+        }
+        None => {}
     }
 
-    let info = load_crate_state()?;
-    update_index()?;
-    let updates = collect_updates(info, &cmd.select_args)?;
+    let config = Config::load()?;
+    let protocol = match cmd.protocol {
+        Some(protocol) => protocol.into(),
+        None => config.crates_io_protocol()?,
+    };
+
+    let settings = Settings::load()?;
+    let rustc = rustc_version::version_meta()?;
+
+    let roots = if cmd.root.is_empty() {
+        vec![config.install_root()?]
+    } else {
+        cmd.root
+    };
+
+    let mut info = load_crate_state(&config, &roots, cmd.format)?;
+    verify_unpin(&info, &cmd.select_args.unpin)?;
+    filter::apply(&mut info, &cmd.select_args.crates, &cmd.select_args.exclude)?;
+    update_indexes(&info, protocol, cmd.format)?;
+    let mut updates = collect_updates(
+        info,
+        protocol,
+        &rustc,
+        &cmd.select_args,
+        &settings,
+        cmd.format,
+    )?;
+
+    let report = if cmd.format == Format::Human {
+        println!();
 
-    println!();
+        registry::print_updates(&updates.registry, &rustc.host);
+        registry::print_outdated(&updates.outdated);
+        registry::print_yanked(&updates.yanked);
+        git::print_updates(
+            &updates.git,
+            &rustc.host,
+            cmd.select_args.git || !cmd.select_args.unpin.is_empty() || settings.any(|pkg| pkg.git),
+        );
+        git::print_pinned(&updates.pinned);
+        path::print_updates(
+            &updates.path,
+            &rustc.host,
+            cmd.select_args.path || settings.any(|pkg| pkg.path),
+        );
+        path::print_missing(&updates.missing);
+        rebuild::print_rebuilds(
+            &updates.rebuild,
+            &rustc.host,
+            cmd.select_args.rebuild_outdated_toolchain,
+        );
+        print_ignored(&updates.ignored);
 
-    registry::print_updates(&updates.registry);
-    git::print_updates(&updates.git, cmd.select_args.git);
-    path::print_updates(&updates.path, cmd.select_args.path);
+        println!();
+        None
+    } else {
+        Some(Report::new(cmd.format, &updates)?)
+    };
 
-    println!();
+    let yanked = !updates.yanked.is_empty();
 
-    if !cmd.dry_run {
-        registry::install_updates(updates.registry.into_iter(), cmd.quiet);
-        git::install_updates(updates.git.into_iter(), cmd.quiet);
-        path::install_updates(updates.path.into_iter(), cmd.quiet);
+    // Held back updates are only listed, but never installed.
+    updates.registry.retain(|_, info| !info.extra.held_back);
+
+    let available = !updates.is_empty();
+
+    if cmd.interactive && select::is_available() {
+        select::select_updates(&mut updates)?;
     }
 
-    Ok(())
+    let options = InstallOptions {
+        quiet: cmd.quiet,
+        jobs: cmd.jobs.into(),
+        target_dir: match &cmd.install_args.target_dir {
+            Some(dir) => Some(dir.clone()),
+            None => settings.cargo().target_dir.clone().or(config.target_dir()?),
+        },
+        cargo: cargo_args(&cmd.install_args, settings.cargo()),
+        log_dir: home::cargo_home()?.join("bup/logs"),
+        backup_dir: backup::dir()?,
+        keep_backups: settings.keep_backups(),
+        smoke_test: (cmd.smoke_test || settings.smoke_test()).then(|| settings.smoke_test_args()),
+        history: history::file()?,
+        rustc: rustc.semver.to_string(),
+        host: rustc.host.clone(),
+        stderr: cmd.format != Format::Human,
+    };
+
+    let results = (!cmd.dry_run).then(|| {
+        let mut results = registry::install_updates(updates.registry.into_iter(), &options);
+        results.extend(git::install_updates(updates.git.into_iter(), &options));
+        results.extend(path::install_updates(updates.path.into_iter(), &options));
+        results.extend(rebuild::install_rebuilds(
+            updates.rebuild.into_iter(),
+            &options,
+        ));
+        results
+    });
+
+    let failed = results
+        .iter()
+        .flatten()
+        .filter(|result| result.outcome == InstallOutcome::Failed)
+        .count();
+
+    match report {
+        Some(report) => report.finish(results.as_deref())?,
+        None => {
+            if let Some(results) = results.as_ref().filter(|results| !results.is_empty()) {
+                print_summary(results);
+            }
+        }
+    }
+
+    Ok(if failed > 0 {
+        Status::InstallFailed
+    } else if yanked && cmd.fail_on_yanked {
+        Status::Yanked
+    } else if available && cmd.exit_code {
+        Status::UpdatesAvailable
+    } else {
+        Status::Success
+    }
+    .into())
+}
+
+/// Final status of a run, that is reported as exit code. Any errors, that prevent the update
+/// check from finishing, result in the exit code `1` instead.
+enum Status {
+    Success,
+    UpdatesAvailable,
+    InstallFailed,
+    Yanked,
+}
+
+impl From<Status> for ExitCode {
+    fn from(value: Status) -> Self {
+        Self::from(match value {
+            Status::Success => 0,
+            Status::UpdatesAvailable => 3,
+            Status::InstallFailed => 4,
+            Status::Yanked => 5,
+        })
+    }
+}
+
+/// Combine the `cargo install` arguments from the command line with the defaults from the
+/// settings.
+fn cargo_args(args: &InstallArgs, settings: &CargoSettings) -> CargoArgs {
+    CargoArgs {
+        locked: args.locked || settings.locked.unwrap_or_default(),
+        offline: args.offline || settings.offline.unwrap_or_default(),
+        force: args.force || settings.force.unwrap_or_default(),
+        build_jobs: args.build_jobs.or(settings.build_jobs),
+        config: settings
+            .config
+            .iter()
+            .chain(&args.config)
+            .cloned()
+            .collect(),
+        args: settings
+            .args
+            .iter()
+            .chain(&args.cargo_args)
+            .cloned()
+            .collect(),
+    }
+}
+
+/// Print a short note about the crates that were skipped due to the settings, so they're not
+/// silently forgotten.
+fn print_ignored(ignored: &BTreeSet<PackageId>) {
+    if ignored.is_empty() {
+        return;
+    }
+
+    let names = ignored
+        .iter()
+        .map(|pkg| pkg.name.as_str())
+        .collect::<Vec<_>>()
+        .join(", ");
+
+    println!(
+        "{} crates: {}",
+        colors::yellow("ignored").bold(),
+        colors::dimmed(names)
+    );
+}
+
+/// Print the outcome of all installations, once all of them finished. Otherwise, any failures
+/// would be buried in cargo's build output.
+fn print_summary(results: &[InstallResult]) {
+    let count = |outcome| results.iter().filter(|r| r.outcome == outcome).count();
+
+    println!("\n{}", results.iter().collect::<SummaryTable>());
+    println!(
+        "{} updated · {} failed · {} skipped\n",
+        colors::green(count(InstallOutcome::Updated)).bold(),
+        colors::red(count(InstallOutcome::Failed)).bold(),
+        colors::yellow(count(InstallOutcome::Skipped)).bold()
+    );
 }
 
 /// Get the current state of all installed crates from the `.crates2.json` file that cargo
-/// maintains for all binaries.
-fn load_crate_state() -> Result<CrateListingV2> {
-    let _guard = progress(format_args!(
-        "{} loading {}",
-        colors::bold("[1/3]"),
-        colors::green("crate state").bold()
-    ));
+/// maintains for all binaries, in each of the installation roots. Packages that are only recorded
+/// in the legacy `.crates.toml` file are included as well.
+///
+/// The listings are merged into one, remembering the root for each package, so updates are
+/// installed into the same location.
+fn load_crate_state(config: &Config, roots: &[PathBuf], format: Format) -> Result<CrateListingV2> {
+    let _guard = progress(
+        format_args!(
+            "{} loading {}",
+            colors::bold("[1/3]"),
+            colors::green("crate state").bold()
+        ),
+        format,
+    );
 
-    let home = home::cargo_home()?;
-    let path = home.join(".crates2.json");
+    let mut state = CrateListingV2::default();
 
-    let file = File::open(path)?;
-    let info = serde_json::from_reader::<_, CrateListingV2>(file)?;
+    for root in roots {
+        let info = match (CrateListingV2::load(root)?, CrateListingV1::load(root)?) {
+            (Some(v2), None) => v2,
+            (Some(mut v2), Some(v1)) => {
+                for (name, mismatch) in v2.merge_v1(v1) {
+                    let reason = match mismatch {
+                        ListingMismatch::MissingV1 => "is missing from `.crates.toml`",
+                        ListingMismatch::MissingV2 => "is missing from `.crates2.json`",
+                        ListingMismatch::Bins => "has differing binaries in each listing",
+                    };
+                    eprintln!(
+                        "\n{} {} {reason}",
+                        colors::yellow("warning:").bold(),
+                        colors::green(name).bold(),
+                    );
+                }
+                v2
+            }
+            (None, Some(v1)) => {
+                let mut v2 = CrateListingV2::default();
+                v2.merge_v1(v1);
+                v2
+            }
+            (None, None) => {
+                eprintln!(
+                    "\n{} no installed crates found in `{}`",
+                    colors::yellow("warning:").bold(),
+                    root.display()
+                );
+                continue;
+            }
+        };
 
-    Ok(info)
+        for (package, mut info) in info.installs {
+            match state.installs.entry(package) {
+                Entry::Vacant(entry) => {
+                    info.root.clone_from(root);
+                    entry.insert(info);
+                }
+                Entry::Occupied(entry) => eprintln!(
+                    "\n{} is installed in multiple roots, only checking the one in `{}`",
+                    colors::green(&entry.key().name).bold(),
+                    entry.get().root.display()
+                ),
+            }
+        }
+    }
+
+    state.assign_registry_names(config);
+
+    Ok(state)
 }
 
-/// Load and update the crates.io registry to the latest version from remote.
-fn update_index() -> Result<()> {
-    let _guard = progress(format_args!(
-        "{} updating {}",
-        colors::bold("[2/3]"),
-        colors::green("crates.io index").bold()
-    ));
+/// Ensure that all crates to unpin are actually installed from a specific Git revision.
+fn verify_unpin(info: &CrateListingV2, unpin: &[String]) -> Result<()> {
+    for name in unpin {
+        let pinned = info.installs.keys().any(|pkg| {
+            pkg.name == *name && matches!(pkg.source_id.kind, SourceKind::Git(GitReference::Rev(_)))
+        });
 
-    let mut index = GitIndex::new_cargo_default()?;
-    index.update()?;
+        if !pinned {
+            bail!("`{name}` is not installed from a pinned git revision");
+        }
+    }
+
+    Ok(())
+}
+
+/// Load and update the indexes of all registries that binaries were installed from, to the latest
+/// version from remote. The crates.io registry is accessed with the given protocol.
+fn update_indexes(info: &CrateListingV2, protocol: Protocol, format: Format) -> Result<()> {
+    let _guard = progress(
+        format_args!(
+            "{} updating {}",
+            colors::bold("[2/3]"),
+            colors::green("registry indexes").bold()
+        ),
+        format,
+    );
+
+    let mut registries = BTreeMap::<&str, Vec<&str>>::new();
+    for package in info.installs.keys() {
+        if package.source_id.kind == SourceKind::Registry {
+            registries
+                .entry(index::location(&package.source_id, protocol))
+                .or_default()
+                .push(&package.name);
+        }
+    }
+
+    for (url, names) in registries {
+        RegistryIndex::open(url)?.update(&names)?;
+    }
 
     Ok(())
 }
@@ -89,64 +389,243 @@ fn update_index() -> Result<()> {
 ///
 /// The update information is collected into several lists, one for each source, as the printable
 /// information and installation logic varies for each source.
-fn collect_updates(info: CrateListingV2, args: &SelectArgs) -> Result<Updates> {
-    let _guard = progress(format_args!(
-        "{} collecting {}",
-        colors::bold("[3/3]"),
-        colors::green("updates").bold()
-    ));
+///
+/// Binaries without any update are checked for being built with an outdated Rust toolchain
+/// instead, if requested.
+fn collect_updates(
+    info: CrateListingV2,
+    protocol: Protocol,
+    rustc: &VersionMeta,
+    args: &SelectArgs,
+    settings: &Settings,
+    format: Format,
+) -> Result<Updates> {
+    let _guard = progress(
+        format_args!(
+            "{} collecting {}",
+            colors::bold("[3/3]"),
+            colors::green("updates").bold()
+        ),
+        format,
+    );
+
+    // Nightly and beta toolchains are considered to support their upcoming stable version.
+    let rust_version = (!args.ignore_rust_version)
+        .then(|| Version::new(rustc.semver.major, rustc.semver.minor, rustc.semver.patch));
 
-    let tls = Arc::new(ThreadLocal::new());
+    let indexes = Indexes::new(protocol);
 
     info.installs
         .into_par_iter()
         .try_fold(Updates::default, |mut updates, (package, info)| {
-            match package.source_id.kind {
+            let settings = settings.package(&package.name);
+            if settings.ignore {
+                updates.ignored.insert(package);
+                return Ok(updates);
+            }
+
+            let pre = args.pre || settings.pre;
+            let mut rebuildable = true;
+
+            let info = match package.source_id.kind {
                 SourceKind::Git(ref git_ref) => {
-                    if let Some(update) = git::check_update(&package, git_ref, args.git)? {
-                        updates.git.insert(package, UpdateInfo::new(info, update));
+                    let unpin = args.unpins(&package.name);
+                    let check = args.git || settings.git || unpin;
+
+                    // Branches are rebuilt from their latest commit, so that's only done once the
+                    // check confirmed that there are no new commits.
+                    rebuildable = matches!(git_ref, GitReference::Tag(_) | GitReference::Rev(_))
+                        || (check && package.source_id.precise.is_some());
+
+                    match git::check_update(
+                        &package,
+                        git_ref,
+                        check,
+                        args.follow_tags || settings.follow_tags,
+                        pre,
+                    )? {
+                        Some(update) if matches!(git_ref, GitReference::Rev(_)) && !unpin => {
+                            updates.pinned.insert(package.clone(), update);
+                            info
+                        }
+                        Some(update) => {
+                            updates.git.insert(package, UpdateInfo::new(info, update));
+                            return Ok(updates);
+                        }
+                        None => info,
                     }
                 }
                 SourceKind::Path => {
-                    if let Some(update) = path::check_update(&package, args.path)? {
-                        updates.path.insert(package, UpdateInfo::new(info, update));
+                    match path::check_update(&package, &info, args.path || settings.path)? {
+                        PathCheck::Changed(update) => {
+                            updates.path.insert(package, UpdateInfo::new(info, update));
+                            return Ok(updates);
+                        }
+                        PathCheck::Missing(dir) => {
+                            updates.missing.insert(package, dir);
+                            return Ok(updates);
+                        }
+                        PathCheck::Unchanged => info,
                     }
                 }
                 SourceKind::Registry => {
-                    let tls = Arc::clone(&tls);
-                    let index = tls.get_or_try(GitIndex::new_cargo_default)?;
+                    // A pin from the settings takes precedence over the recorded requirement.
+                    let version_req = if let Some(pin) = settings.pin {
+                        Some(pin.clone())
+                    } else if args.respects_req(&package.name) || settings.respect_req {
+                        info.version_req(&package.version)?
+                    } else {
+                        None
+                    };
+
+                    let check = indexes.with(&package.source_id, |index| {
+                        registry::check_update(
+                            index,
+                            &package,
+                            version_req.as_ref(),
+                            rust_version.as_ref(),
+                            pre,
+                        )
+                    })?;
 
-                    if let Some(update) = registry::check_update(index, &package, args.pre)? {
-                        updates
-                            .registry
-                            .insert(package, UpdateInfo::new(info, update));
+                    if check.yanked {
+                        updates.yanked.insert(package.clone());
+                    }
+
+                    match check.update {
+                        None if check.latest.is_some() || check.incompatible.is_some() => {
+                            updates.outdated.insert(
+                                package.clone(),
+                                OutdatedInfo {
+                                    latest: check.latest,
+                                    incompatible: check.incompatible,
+                                },
+                            );
+                            info
+                        }
+                        Some(mut update) => {
+                            // Cargo only gets to see recorded requirements. A pin from the
+                            // settings selects the exact version instead, so it doesn't end up in
+                            // cargo's listing.
+                            if settings.pin.is_some() {
+                                update.version_req = None;
+                            }
+                            update.held_back = args.only.or(settings.only).is_some_and(|only| {
+                                UpdateLevel::of(&package.version, &update.version) > only
+                            });
+                            updates
+                                .registry
+                                .insert(package, UpdateInfo::new(info, update));
+                            return Ok(updates);
+                        }
+                        None => info,
                     }
                 }
+            };
+
+            if args.rebuild_outdated_toolchain && rebuildable {
+                if let Some(rebuild) = rebuild::check_rebuild(&info, rustc) {
+                    updates
+                        .rebuild
+                        .insert(package, UpdateInfo::new(info, rebuild));
+                }
             }
+
             anyhow::Ok(updates)
         })
         .try_reduce(Updates::default, |mut a, mut b| {
             a.registry.append(&mut b.registry);
             a.git.append(&mut b.git);
             a.path.append(&mut b.path);
+            a.pinned.append(&mut b.pinned);
+            a.rebuild.append(&mut b.rebuild);
+            a.outdated.append(&mut b.outdated);
+            a.yanked.append(&mut b.yanked);
+            a.missing.append(&mut b.missing);
+            a.ignored.append(&mut b.ignored);
             Ok(a)
         })
 }
 
 /// A guard created by [`progress`], that will finish the currently written line with a `done`
 /// at the end, when this guard is dropped.
-struct ProgressGuard;
+struct ProgressGuard {
+    stderr: bool,
+}
 
 impl Drop for ProgressGuard {
     fn drop(&mut self) {
-        println!("done");
+        if self.stderr {
+            eprintln!("done");
+        } else {
+            println!("done");
+        }
     }
 }
 
 /// Print the given message, without writing a new line. Instead a [`ProgressGuard`] is returned,
 /// that will finish the current line when dropped.
-fn progress(msg: fmt::Arguments) -> ProgressGuard {
-    print!("{msg}... ");
-    std::io::stdout().flush().ok();
-    ProgressGuard
+///
+/// For machine readable formats, the message is written to the standard error stream, to keep
+/// the standard output free for the report.
+fn progress(msg: fmt::Arguments, format: Format) -> ProgressGuard {
+    let stderr = format != Format::Human;
+
+    if stderr {
+        eprint!("{msg}... ");
+        std::io::stderr().flush().ok();
+    } else {
+        print!("{msg}... ");
+        std::io::stdout().flush().ok();
+    }
+
+    ProgressGuard { stderr }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn combine_cargo_args() {
+        let settings = toml::from_str::<CargoSettings>(
+            r#"
+            offline = true
+            force = false
+            build-jobs = 2
+            config = ["net.git-fetch-with-cli=true"]
+            args = ["--debug"]
+            "#,
+        )
+        .unwrap();
+        let args = InstallArgs {
+            locked: true,
+            offline: false,
+            force: false,
+            build_jobs: Some(8),
+            config: vec!["build.rustflags=[]".to_owned()],
+            target_dir: None,
+            cargo_args: vec!["--timings".to_owned()],
+        };
+
+        let cargo = cargo_args(&args, &settings);
+        assert!(cargo.locked);
+        assert!(cargo.offline);
+        assert!(!cargo.force);
+        assert_eq!(Some(8), cargo.build_jobs);
+        assert_eq!(
+            vec!["net.git-fetch-with-cli=true", "build.rustflags=[]"],
+            cargo.config
+        );
+        assert_eq!(vec!["--debug", "--timings"], cargo.args);
+
+        let cargo = cargo_args(
+            &InstallArgs {
+                build_jobs: None,
+                ..args
+            },
+            &settings,
+        );
+        assert_eq!(Some(2), cargo.build_jobs);
+    }
 }
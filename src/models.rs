@@ -1,17 +1,53 @@
 //! Data structures used throughout the crate.
 
-use std::collections::BTreeMap;
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    path::PathBuf,
+    time::{Duration, SystemTime},
+};
 
+use anyhow::Result;
+use clap::ValueEnum;
 use gix::ObjectId;
-use semver::Version;
+use rustc_version::VersionMeta;
+use semver::{Version, VersionReq};
+use serde::{Deserialize, Serialize};
 
-use crate::cargo::{InstallInfo, PackageId};
+use crate::{
+    cargo::{InstallInfo, PackageId},
+    common::{self, CmdOutput},
+};
 
 #[derive(Default)]
 pub struct Updates {
     pub registry: BTreeMap<PackageId, UpdateInfo<RegistryInfo>>,
     pub git: BTreeMap<PackageId, UpdateInfo<GitInfo>>,
     pub path: BTreeMap<PackageId, UpdateInfo<PathInfo>>,
+    /// Git packages pinned to a revision, that are behind the remote default branch. Only listed
+    /// for information, unless unpinned.
+    pub pinned: BTreeMap<PackageId, GitInfo>,
+    /// Packages without update, that were built with an older Rust toolchain.
+    pub rebuild: BTreeMap<PackageId, UpdateInfo<RebuildInfo>>,
+    /// Registry packages without any installable update, that still have newer versions. Only
+    /// listed for information.
+    pub outdated: BTreeMap<PackageId, OutdatedInfo>,
+    /// Registry packages, whose installed version has been yanked from the registry.
+    pub yanked: BTreeSet<PackageId>,
+    /// Path packages, whose source directory doesn't exist anymore. Only listed for information.
+    pub missing: BTreeMap<PackageId, PathBuf>,
+    /// Packages that were skipped entirely, as they're ignored in the settings.
+    pub ignored: BTreeSet<PackageId>,
+}
+
+impl Updates {
+    /// Whether there is nothing to install. Pinned, outdated, yanked, missing and ignored packages
+    /// are only informational, and therefore not considered.
+    pub fn is_empty(&self) -> bool {
+        self.registry.is_empty()
+            && self.git.is_empty()
+            && self.path.is_empty()
+            && self.rebuild.is_empty()
+    }
 }
 
 pub struct UpdateInfo<T> {
@@ -29,7 +65,65 @@ impl<T> UpdateInfo<T> {
 }
 
 pub struct RegistryInfo {
+    /// The version to update to.
+    pub version: Version,
+    /// Version requirement recorded at install time, if updates were restricted to it.
+    pub version_req: Option<VersionReq>,
+    /// Latest overall version, if it lies outside the version requirement.
+    pub latest: Option<Version>,
+    /// Whether the currently installed version has been yanked.
+    pub yanked: bool,
+    /// Newer version that was skipped, as it requires a newer Rust toolchain.
+    pub incompatible: Option<IncompatibleVersion>,
+    /// Whether the update exceeds the allowed update level, and is only listed but not installed.
+    pub held_back: bool,
+}
+
+/// Newer versions of a registry package, that can't be installed.
+pub struct OutdatedInfo {
+    /// Latest overall version, that lies outside the version requirement.
+    pub latest: Option<Version>,
+    /// Newer version that requires a newer Rust toolchain.
+    pub incompatible: Option<IncompatibleVersion>,
+}
+
+/// A version that can't be built with the active Rust toolchain.
+#[derive(Clone)]
+pub struct IncompatibleVersion {
     pub version: Version,
+    /// Minimum Rust version required to build it.
+    pub rust_version: Version,
+}
+
+/// Level of an update, following cargo's flavor of semantic versioning, where the left-most
+/// non-zero component is considered the major version.
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, PartialEq, Eq, PartialOrd, Ord, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum UpdateLevel {
+    /// Bug fixes only.
+    Patch,
+    /// Backwards compatible changes.
+    Minor,
+    /// Breaking changes.
+    Major,
+}
+
+impl UpdateLevel {
+    pub fn of(current: &Version, latest: &Version) -> Self {
+        if current.major != latest.major || (current.major == 0 && current.minor == 0) {
+            Self::Major
+        } else if current.major == 0 {
+            if current.minor != latest.minor {
+                Self::Major
+            } else {
+                Self::Minor
+            }
+        } else if current.minor != latest.minor {
+            Self::Minor
+        } else {
+            Self::Patch
+        }
+    }
 }
 
 pub struct GitInfo {
@@ -40,7 +134,7 @@ pub struct GitInfo {
     pub target: GitTarget,
 }
 
-#[derive(Default)]
+#[derive(Default, Serialize)]
 pub struct GitChanges {
     pub commits: usize,
     pub files_changed: usize,
@@ -51,6 +145,131 @@ pub struct GitChanges {
 pub enum GitTarget {
     Default,
     Branch(String),
+    Tag(String),
 }
 
-pub struct PathInfo {}
+pub struct PathInfo {
+    /// Local directory that the package was installed from.
+    pub path: PathBuf,
+    /// Amount of files modified after the binaries were installed.
+    pub changed_files: usize,
+    /// Modification time of the most recently changed file.
+    pub newest_change: SystemTime,
+    /// State of the Git worktree, if the directory is part of one.
+    pub git: Option<PathGitStatus>,
+}
+
+pub struct PathGitStatus {
+    /// Currently checked out branch, unless detached.
+    pub branch: Option<String>,
+    /// The commit that HEAD points to.
+    pub head: ObjectId,
+    /// Whether tracked files contain uncommitted changes.
+    pub dirty: bool,
+    /// Amount of commits on HEAD that aren't reachable from the installed commit.
+    pub ahead: usize,
+}
+
+pub struct RebuildInfo {
+    /// Toolchain that the binary was originally built with.
+    pub old_rustc: VersionMeta,
+    /// Currently active toolchain.
+    pub new_rustc: VersionMeta,
+}
+
+/// Outcome of installing a single update.
+pub struct InstallResult {
+    pub package: PackageId,
+    /// Short description of the change, like the old and new version.
+    pub change: String,
+    pub outcome: InstallOutcome,
+    /// Time it took cargo to install the update.
+    pub duration: Duration,
+    /// Reason for a failed or skipped installation.
+    pub error: Option<String>,
+    /// First error lines of cargo's output, if the installation failed.
+    pub details: Vec<String>,
+    /// File with cargo's full output, if it was captured.
+    pub log: Option<PathBuf>,
+}
+
+#[derive(Clone, Copy, Deserialize, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum InstallOutcome {
+    Updated,
+    Failed,
+    /// The installation wasn't attempted at all.
+    Skipped,
+}
+
+impl InstallResult {
+    pub fn new(package: PackageId, change: String, result: Result<CmdOutput>) -> Self {
+        let (outcome, duration, error, details, log) = match result {
+            Ok(output) if output.status.success() => (
+                InstallOutcome::Updated,
+                output.duration,
+                None,
+                Vec::new(),
+                output.log,
+            ),
+            Ok(output) => (
+                InstallOutcome::Failed,
+                output.duration,
+                Some(format!("cargo failed with {}", output.status)),
+                common::error_lines(&output.stderr),
+                output.log,
+            ),
+            Err(e) => (
+                InstallOutcome::Failed,
+                Duration::ZERO,
+                Some(format!("{e:#}")),
+                Vec::new(),
+                None,
+            ),
+        };
+
+        Self {
+            package,
+            change,
+            outcome,
+            duration,
+            error,
+            details,
+            log,
+        }
+    }
+
+    pub fn skipped(package: PackageId, change: String, reason: &anyhow::Error) -> Self {
+        Self {
+            package,
+            change,
+            outcome: InstallOutcome::Skipped,
+            duration: Duration::ZERO,
+            error: Some(format!("{reason:#}")),
+            details: Vec::new(),
+            log: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn update_levels() {
+        let level = |current, latest| {
+            UpdateLevel::of(
+                &Version::parse(current).unwrap(),
+                &Version::parse(latest).unwrap(),
+            )
+        };
+
+        assert_eq!(UpdateLevel::Patch, level("1.0.0", "1.0.1"));
+        assert_eq!(UpdateLevel::Minor, level("1.0.0", "1.2.0"));
+        assert_eq!(UpdateLevel::Major, level("1.2.0", "2.0.0"));
+        assert_eq!(UpdateLevel::Minor, level("0.1.0", "0.1.5"));
+        assert_eq!(UpdateLevel::Major, level("0.1.0", "0.2.0"));
+        assert_eq!(UpdateLevel::Major, level("0.0.1", "0.0.2"));
+    }
+}
@@ -1,25 +1,226 @@
 //! Handling of crates that were installed from **local paths**.
 
-use std::collections::{BTreeMap, BTreeSet};
+use std::{
+    collections::BTreeMap,
+    fs,
+    io::ErrorKind,
+    path::{Path, PathBuf},
+    process::Command,
+    time::SystemTime,
+};
 
 use anstream::println;
-use anyhow::Result;
+use anyhow::{bail, Context, Result};
+use gix::{traverse::commit::simple::Sorting, Commit, ObjectId, Repository};
+use serde::Deserialize;
 
 use crate::{
-    cargo::PackageId,
-    colors,
-    models::{PathInfo, UpdateInfo},
+    cargo::{InstallInfo, PackageId},
+    colors, common,
+    install::{self, InstallJob, InstallOptions},
+    models::{InstallResult, PathGitStatus, PathInfo, UpdateInfo},
+    table::PathTable,
 };
 
-pub(crate) fn check_update(_package: &PackageId, _path: bool) -> Result<Option<PathInfo>> {
-    if !_path {
+/// Result of checking a local path package for changes.
+pub(crate) enum PathCheck {
+    /// Nothing changed since the installation, or the check is disabled.
+    Unchanged,
+    /// Files were modified after the binaries were installed.
+    Changed(PathInfo),
+    /// The directory that the package was installed from doesn't exist anymore.
+    Missing(PathBuf),
+}
+
+/// Check whether any of the files in the package's directory were modified after its binaries
+/// were installed. The build output in `target` as well as hidden files and directories (like
+/// `.git`) are ignored. For members of a workspace, the lockfile in the workspace root is
+/// considered as well.
+pub(crate) fn check_update(
+    package: &PackageId,
+    info: &InstallInfo,
+    path: bool,
+) -> Result<PathCheck> {
+    if !path {
+        return Ok(PathCheck::Unchanged);
+    }
+
+    let dir = package
+        .source_id
+        .url
+        .to_file_path()
+        .ok()
+        .context("invalid local path")?;
+
+    // Nothing can be rebuilt from a directory that is gone.
+    if !dir.is_dir() {
+        return Ok(PathCheck::Missing(dir));
+    }
+
+    // The oldest binary determines the install time, in case they were installed separately.
+    let mut installed = None::<SystemTime>;
+    for bin in &info.bins {
+        match fs::metadata(info.root.join("bin").join(bin)) {
+            Ok(meta) => {
+                let modified = meta.modified()?;
+                installed = Some(installed.map_or(modified, |i| i.min(modified)));
+            }
+            // Missing binaries need a re-install in any case.
+            Err(e) if e.kind() == ErrorKind::NotFound => {
+                installed = Some(SystemTime::UNIX_EPOCH);
+                break;
+            }
+            Err(e) => return Err(e.into()),
+        }
+    }
+
+    let changes = Changes::scan(&dir, installed.unwrap_or(SystemTime::UNIX_EPOCH))
+        .with_context(|| format!("failed scanning `{}`", dir.display()))?;
+
+    let Some(newest) = changes.newest else {
+        return Ok(PathCheck::Unchanged);
+    };
+
+    let git = git_status(&dir, changes.since)
+        .with_context(|| format!("failed reading git status of `{}`", dir.display()))?;
+
+    Ok(PathCheck::Changed(PathInfo {
+        path: dir,
+        changed_files: changes.files,
+        newest_change: newest,
+        git,
+    }))
+}
+
+/// Collect the state of the Git worktree that the directory is part of, if any. As cargo doesn't
+/// record the commit a path package was installed from, it's looked up by the install time.
+fn git_status(dir: &Path, installed: SystemTime) -> Result<Option<PathGitStatus>> {
+    use gix::traverse::commit::topo::Builder;
+
+    let Ok(repo) = gix::discover(dir) else {
         return Ok(None);
+    };
+    let Ok(head) = repo.head_commit() else {
+        return Ok(None);
+    };
+
+    let installed = installed
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs() as i64);
+
+    // Only count the commits that aren't reachable from the installed one, like
+    // `git rev-list installed..HEAD`, so merged branches with older commits are counted as well.
+    let ahead = match installed_commit(&repo, &head, installed)? {
+        Some(old) => Builder::from_iters(&repo.objects, [head.id], Some([old]))
+            .build()?
+            .map_while(Result::ok)
+            .count(),
+        None => head.ancestors().all()?.map_while(Result::ok).count(),
+    };
+
+    Ok(Some(PathGitStatus {
+        branch: repo.head_name()?.map(|name| name.shorten().to_string()),
+        head: head.id,
+        dirty: repo.is_dirty()?,
+        ahead,
+    }))
+}
+
+/// Find the commit that `HEAD` pointed to at the install time. The reflog tells where `HEAD` was
+/// at that point, otherwise the newest commit on `HEAD` that was created before is taken.
+///
+/// Nothing is returned, if all commits were created after the installation.
+fn installed_commit(
+    repo: &Repository,
+    head: &Commit<'_>,
+    installed: i64,
+) -> Result<Option<ObjectId>> {
+    if let Some(lines) = repo.head()?.log_iter().rev()? {
+        for line in lines.map_while(Result::ok) {
+            if line.signature.time.seconds <= installed {
+                if !line.new_oid.is_null() && repo.has_object(line.new_oid) {
+                    return Ok(Some(line.new_oid));
+                }
+                break;
+            }
+        }
+    }
+
+    Ok(head
+        .ancestors()
+        .sorting(Sorting::ByCommitTimeNewestFirst)
+        .all()?
+        .map_while(Result::ok)
+        .find(|info| info.commit_time.is_some_and(|time| time <= installed))
+        .map(|info| info.id))
+}
+
+/// Accumulator for files that were modified after a certain point in time.
+struct Changes {
+    since: SystemTime,
+    files: usize,
+    newest: Option<SystemTime>,
+}
+
+impl Changes {
+    /// Scan the package directory, as well as the workspace lockfile if the package doesn't have
+    /// its own. Cargo uses the lockfile of the workspace root for all its members.
+    fn scan(dir: &Path, since: SystemTime) -> Result<Self> {
+        let mut changes = Self {
+            since,
+            files: 0,
+            newest: None,
+        };
+        changes.collect(dir)?;
+
+        if !dir.join("Cargo.lock").is_file() {
+            let lockfile = dir
+                .ancestors()
+                .skip(1)
+                .map(|parent| parent.join("Cargo.lock"))
+                .find(|lockfile| lockfile.is_file());
+
+            if let Some(lockfile) = lockfile {
+                changes.add(fs::metadata(lockfile)?.modified()?);
+            }
+        }
+
+        Ok(changes)
     }
 
-    Ok(Some(PathInfo {}))
+    fn collect(&mut self, dir: &Path) -> Result<()> {
+        for entry in fs::read_dir(dir)? {
+            let entry = entry?;
+            let name = entry.file_name();
+
+            if name.to_string_lossy().starts_with('.') || name == "target" {
+                continue;
+            }
+
+            let meta = entry.metadata()?;
+            if meta.is_dir() {
+                self.collect(&entry.path())?;
+            } else {
+                self.add(meta.modified()?);
+            }
+        }
+
+        Ok(())
+    }
+
+    fn add(&mut self, modified: SystemTime) {
+        if modified > self.since {
+            self.files += 1;
+            self.newest = Some(self.newest.map_or(modified, |n| n.max(modified)));
+        }
+    }
 }
 
-pub(crate) fn print_updates(updates: &BTreeMap<PackageId, UpdateInfo<PathInfo>>, enabled: bool) {
+pub(crate) fn print_updates(
+    updates: &BTreeMap<PackageId, UpdateInfo<PathInfo>>,
+    host: &str,
+    enabled: bool,
+) {
     if !enabled {
         println!(
             "{} crate updates {}",
@@ -29,39 +230,204 @@ pub(crate) fn print_updates(updates: &BTreeMap<PackageId, UpdateInfo<PathInfo>>,
     } else if updates.is_empty() {
         println!("no {} crates", colors::green("local path"));
     } else {
-        println!("<<< Updates from {} >>>", colors::green("local paths"));
-
-        let paths = updates
+        let table = updates
             .iter()
-            .map(|(pkg, _)| pkg.name.as_str())
-            .collect::<BTreeSet<_>>();
+            .map(|(pkg, info)| {
+                (
+                    pkg.name.as_str(),
+                    &info.extra,
+                    info.install_info.cross_target(host),
+                )
+            })
+            .collect::<PathTable>();
 
-        let width = paths
-            .iter()
-            .max_by_key(|n| n.len())
-            .map(|n| n.len())
-            .unwrap_or(4);
-
-        println!("\nName");
-        println!("{}", "─".repeat(width));
+        println!("\n{table}\n");
+    }
+}
 
-        for name in paths {
-            println!("{name}");
-        }
+/// Warn about packages, whose directory is gone. They can't be checked or rebuilt anymore, but
+/// shouldn't silently disappear from the listing either.
+pub(crate) fn print_missing(missing: &BTreeMap<PackageId, PathBuf>) {
+    for (pkg, dir) in missing {
+        println!(
+            "{} {} was installed from `{}`, which doesn't exist anymore",
+            colors::yellow("warning:").bold(),
+            colors::green(&pkg.name).bold(),
+            dir.display()
+        );
     }
 }
 
 pub(crate) fn install_updates(
     updates: impl ExactSizeIterator<Item = (PackageId, UpdateInfo<PathInfo>)>,
-    _quiet: bool,
-) {
+    options: &InstallOptions,
+) -> Vec<InstallResult> {
     let count = updates.len();
+    if count == 0 {
+        return Vec::new();
+    }
 
-    if count > 0 {
-        println!(
-            "start installing {} {} updates",
-            colors::blue(count).bold(),
-            colors::green("local path").bold()
+    options.message(format_args!(
+        "start installing {} {} updates\n",
+        colors::blue(count).bold(),
+        colors::green("local path").bold()
+    ));
+
+    let jobs = updates
+        .map(|(pkg, info)| InstallJob {
+            change: format!("{} files changed", info.extra.changed_files),
+            from: pkg.version.to_string(),
+            to: None,
+            message: format!(
+                "updating {} {} with {} changed files",
+                colors::green(&pkg.name).bold(),
+                colors::blue(&pkg.version).bold(),
+                colors::blue(info.extra.changed_files).bold()
+            ),
+            cmd: verify_package(&info.extra.path, &pkg.name)
+                .map(|()| cargo_install(&info.extra.path, &info.install_info, &options.host)),
+            package: pkg,
+            info: info.install_info,
+        })
+        .collect();
+
+    install::run(jobs, "installing", options)
+}
+
+fn cargo_install(dir: &Path, info: &InstallInfo, host: &str) -> Command {
+    let mut cmd = Command::new("cargo");
+    cmd.arg("install");
+    cmd.arg("--path");
+    cmd.arg(dir);
+
+    common::apply_cmd_args(&mut cmd, info, host);
+    cmd
+}
+
+/// Ensure the directory still exists and contains the package, as it might have been moved or
+/// changed since the installation.
+pub(crate) fn verify_package(dir: &Path, name: &str) -> Result<()> {
+    #[derive(Deserialize)]
+    struct Manifest {
+        package: Option<ManifestPackage>,
+    }
+
+    #[derive(Deserialize)]
+    struct ManifestPackage {
+        name: String,
+    }
+
+    if !dir.is_dir() {
+        bail!("directory `{}` doesn't exist anymore", dir.display());
+    }
+
+    let manifest = match fs::read_to_string(dir.join("Cargo.toml")) {
+        Ok(manifest) => manifest,
+        Err(e) if e.kind() == ErrorKind::NotFound => {
+            bail!(
+                "directory `{}` doesn't contain a package anymore",
+                dir.display()
+            );
+        }
+        Err(e) => return Err(e.into()),
+    };
+
+    let manifest = toml::from_str::<Manifest>(&manifest)
+        .with_context(|| format!("failed parsing manifest in `{}`", dir.display()))?;
+
+    match manifest.package {
+        Some(package) if package.name == name => Ok(()),
+        Some(package) => bail!(
+            "directory `{}` contains package `{}` instead of `{name}` now",
+            dir.display(),
+            package.name
+        ),
+        None => bail!(
+            "directory `{}` doesn't contain a package anymore",
+            dir.display()
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    #[test]
+    fn scan_changes() {
+        let root = tempfile::tempdir().unwrap();
+        let dir = root.path().join("crates/foo");
+        for sub in ["src", ".git", "target/release"] {
+            fs::create_dir_all(dir.join(sub)).unwrap();
+        }
+        for file in [
+            "Cargo.toml",
+            "src/main.rs",
+            ".env",
+            ".git/HEAD",
+            "target/release/foo",
+        ] {
+            fs::write(dir.join(file), "").unwrap();
+        }
+
+        let before = SystemTime::now() - Duration::from_secs(60);
+        let changes = Changes::scan(&dir, before).unwrap();
+        assert_eq!(2, changes.files);
+        assert!(changes.newest.is_some());
+
+        let after = SystemTime::now() + Duration::from_secs(60);
+        assert_eq!(0, Changes::scan(&dir, after).unwrap().files);
+
+        // The workspace lockfile counts, unless the package has its own.
+        fs::write(root.path().join("Cargo.lock"), "").unwrap();
+        assert_eq!(3, Changes::scan(&dir, before).unwrap().files);
+
+        fs::write(dir.join("Cargo.lock"), "").unwrap();
+        assert_eq!(3, Changes::scan(&dir, before).unwrap().files);
+    }
+
+    #[test]
+    fn git_ahead_and_dirty() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("Cargo.toml"), "").unwrap();
+
+        let git = |time: u64, args: &[&str]| {
+            let date = format!("@{time} +0000");
+            let status = Command::new("git")
+                .args(["-c", "user.name=test", "-c", "user.email=test@localhost"])
+                .args(args)
+                .env("GIT_AUTHOR_DATE", &date)
+                .env("GIT_COMMITTER_DATE", &date)
+                .current_dir(dir.path())
+                .status()
+                .unwrap();
+            assert!(status.success());
+        };
+        git(100, &["init", "--quiet", "--initial-branch", "main"]);
+        git(100, &["add", "."]);
+        git(100, &["commit", "--quiet", "-m", "installed"]);
+
+        // A branch with commits older than the installation, that is merged only afterwards.
+        git(120, &["checkout", "--quiet", "-b", "feature"]);
+        git(
+            120,
+            &["commit", "--quiet", "--allow-empty", "-m", "feature"],
+        );
+        git(130, &["checkout", "--quiet", "main"]);
+        git(
+            200,
+            &["merge", "--quiet", "--no-ff", "-m", "merge", "feature"],
         );
+
+        let installed = SystemTime::UNIX_EPOCH + Duration::from_secs(150);
+        let status = git_status(dir.path(), installed).unwrap().unwrap();
+        assert_eq!(Some("main"), status.branch.as_deref());
+        assert_eq!(2, status.ahead);
+        assert!(!status.dirty);
+
+        fs::write(dir.path().join("Cargo.toml"), "[package]").unwrap();
+        assert!(git_status(dir.path(), installed).unwrap().unwrap().dirty);
     }
 }
@@ -0,0 +1,187 @@
+//! Handling of binaries that were **built with an outdated Rust toolchain**, which are re-installed
+//! at their current version with the active toolchain.
+
+use std::{collections::BTreeMap, process::Command};
+
+use anstream::println;
+use anyhow::{Context, Result};
+use rustc_version::VersionMeta;
+
+use crate::{
+    cargo::{GitReference, InstallInfo, PackageId, SourceKind},
+    colors, common,
+    install::{self, InstallJob, InstallOptions},
+    models::{InstallResult, RebuildInfo, UpdateInfo},
+    path, registry,
+    table::RebuildTable,
+};
+
+/// Check whether the binary was built with an older compiler than the active one. Binaries without
+/// any record of the compiler are left alone.
+pub(crate) fn check_rebuild(info: &InstallInfo, rustc: &VersionMeta) -> Option<RebuildInfo> {
+    let old = info.rustc.as_ref()?;
+
+    let outdated = old.semver < rustc.semver
+        || (old.semver == rustc.semver && old.commit_hash != rustc.commit_hash);
+
+    outdated.then(|| RebuildInfo {
+        old_rustc: old.clone(),
+        new_rustc: rustc.clone(),
+    })
+}
+
+pub(crate) fn print_rebuilds(
+    rebuilds: &BTreeMap<PackageId, UpdateInfo<RebuildInfo>>,
+    host: &str,
+    enabled: bool,
+) {
+    if !enabled {
+        return;
+    }
+
+    if rebuilds.is_empty() {
+        println!("no {} binaries", colors::green("outdated toolchain"));
+    } else {
+        let table = rebuilds
+            .iter()
+            .map(|(pkg, info)| {
+                (
+                    pkg.name.as_str(),
+                    &pkg.version,
+                    &info.extra,
+                    info.install_info.cross_target(host),
+                )
+            })
+            .collect::<RebuildTable>();
+
+        println!("\n{table}\n");
+    }
+}
+
+pub(crate) fn install_rebuilds(
+    rebuilds: impl ExactSizeIterator<Item = (PackageId, UpdateInfo<RebuildInfo>)>,
+    options: &InstallOptions,
+) -> Vec<InstallResult> {
+    let count = rebuilds.len();
+    if count == 0 {
+        return Vec::new();
+    }
+
+    options.message(format_args!(
+        "start rebuilding {} {} binaries\n",
+        colors::blue(count).bold(),
+        colors::green("outdated toolchain").bold()
+    ));
+
+    let jobs = rebuilds
+        .map(|(pkg, info)| InstallJob {
+            change: format!(
+                "rustc {} ➞ {}",
+                info.extra.old_rustc.semver, info.extra.new_rustc.semver
+            ),
+            from: pkg.version.to_string(),
+            to: Some(pkg.version.to_string()),
+            message: format!(
+                "rebuilding {} {} with rustc {}",
+                colors::green(&pkg.name).bold(),
+                colors::blue(&pkg.version).bold(),
+                colors::blue(&info.extra.new_rustc.semver).bold()
+            ),
+            cmd: cargo_install(&pkg, &info.install_info, &options.host),
+            package: pkg,
+            info: info.install_info,
+        })
+        .collect();
+
+    install::run(jobs, "rebuilding", options)
+}
+
+/// Re-install the package from the same source it was installed from. Git sources that follow a
+/// branch are installed from that branch again, so cargo keeps tracking it. These are only rebuilt
+/// once the Git check confirmed that the branch has no new commits.
+///
+/// Registry packages keep their recorded version requirement, capped at the installed version.
+/// Path packages are skipped, if their directory doesn't contain the package anymore.
+fn cargo_install(package: &PackageId, info: &InstallInfo, host: &str) -> Result<Command> {
+    let mut cmd = Command::new("cargo");
+    cmd.args(["install", "--force"]);
+
+    match &package.source_id.kind {
+        SourceKind::Registry => {
+            cmd.arg(&package.name);
+            cmd.arg("--version");
+            match info.version_req(&package.version)? {
+                Some(req) => cmd.arg(registry::capped_req(&req, &package.version)),
+                None => cmd.arg(format!("={}", package.version)),
+            };
+            registry::apply_registry_args(&mut cmd, &package.source_id);
+        }
+        SourceKind::Git(git_ref) => {
+            cmd.arg(&package.name);
+            cmd.args(["--git", package.source_id.url.as_str()]);
+
+            match git_ref {
+                GitReference::Tag(t) => cmd.args(["--tag", t]),
+                GitReference::Rev(r) => cmd.args(["--rev", r]),
+                GitReference::Branch(b) => cmd.args(["--branch", b]),
+                GitReference::DefaultBranch => &mut cmd,
+            };
+        }
+        SourceKind::Path => {
+            let path = package
+                .source_id
+                .url
+                .to_file_path()
+                .ok()
+                .context("invalid local path")?;
+            path::verify_package(&path, &package.name)?;
+            cmd.arg("--path");
+            cmd.arg(path);
+        }
+    }
+
+    common::apply_cmd_args(&mut cmd, info, host);
+    Ok(cmd)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cargo::CRATES_IO_INDEX;
+
+    #[test]
+    fn keep_version_req() {
+        let package = serde_json::from_value::<PackageId>(
+            format!("foo 1.2.3 (registry+{CRATES_IO_INDEX})").into(),
+        )
+        .unwrap();
+        let version = |version_req: Option<&str>| {
+            let info = InstallInfo {
+                version_req: version_req.map(ToOwned::to_owned),
+                ..InstallInfo::default()
+            };
+            let cmd = cargo_install(&package, &info, "").unwrap();
+            cmd.get_args()
+                .skip_while(|arg| *arg != "--version")
+                .nth(1)
+                .unwrap()
+                .to_string_lossy()
+                .into_owned()
+        };
+
+        assert_eq!("=1.2.3", version(None));
+        assert_eq!("^1, <=1.2.3", version(Some("^1")));
+        assert_eq!("=1.2.3, <=1.2.3", version(Some("1.2.3")));
+    }
+
+    #[test]
+    fn skip_missing_path() {
+        let dir = tempfile::tempdir().unwrap();
+        let url = url::Url::from_directory_path(dir.path().join("foo")).unwrap();
+        let package =
+            serde_json::from_value::<PackageId>(format!("foo 0.1.0 (path+{url})").into()).unwrap();
+
+        let e = cargo_install(&package, &InstallInfo::default(), "").unwrap_err();
+        assert!(e.to_string().contains("doesn't exist anymore"));
+    }
+}
@@ -1,99 +1,400 @@
-//! Handling of crates that were installed from **the main <https://crates.io> registry**.
+//! Handling of crates that were installed from **crate registries**, like the main
+//! <https://crates.io> registry or any alternative registry.
 
-use std::{collections::BTreeMap, process::Command};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    process::Command,
+};
 
-use anstream::{eprintln, println};
-use anyhow::{Context, Result};
-use crates_index::GitIndex;
-use semver::Version;
+use anstream::println;
+use anyhow::Result;
+use crates_index::Crate;
+use semver::{Version, VersionReq};
 
 use crate::{
-    cargo::{InstallInfo, PackageId},
+    cargo::{InstallInfo, PackageId, SourceId},
     colors, common,
-    models::{RegistryInfo, UpdateInfo},
-    table::RegistryTable,
+    index::RegistryIndex,
+    install::{self, InstallJob, InstallOptions},
+    models::{IncompatibleVersion, InstallResult, OutdatedInfo, RegistryInfo, UpdateInfo},
+    table::{OutdatedTable, RegistryTable, YankedTable},
 };
 
-/// Remote Git repository location for the main <https://crates.io> registry.
-const CRATES_IO_GIT_URL: &str = "https://github.com/rust-lang/crates.io-index";
+/// Outcome of checking a single package against its registry index.
+pub(crate) struct RegistryCheck {
+    /// Available update, if any.
+    pub update: Option<RegistryInfo>,
+    /// Latest overall version, if it's newer than the installed version but lies outside the
+    /// version requirement. Reported regardless of any available update.
+    pub latest: Option<Version>,
+    /// Highest version that is newer than both the installed version and the update, but requires
+    /// a newer Rust toolchain. Reported regardless of any available update.
+    pub incompatible: Option<IncompatibleVersion>,
+    /// Whether the currently installed version has been yanked.
+    pub yanked: bool,
+}
 
+/// Find the highest non-yanked version of the package, that is newer than the installed version.
+/// If a version requirement is given, only matching versions are considered, but the latest
+/// overall version is reported as well.
+///
+/// If the active Rust toolchain version is given, versions that require a newer toolchain are
+/// skipped as well, but the highest of them is reported.
 pub(crate) fn check_update(
-    index: &GitIndex,
+    index: &RegistryIndex,
     package: &PackageId,
+    version_req: Option<&VersionReq>,
+    rustc: Option<&Version>,
     pre: bool,
-) -> Result<Option<RegistryInfo>> {
-    if package.source_id.url.as_str() != CRATES_IO_GIT_URL {
-        // Currently only support the main crates.io registry.
-        return Ok(None);
-    }
+) -> Result<RegistryCheck> {
+    let krate = index.crate_(&package.name)?;
+    check_versions(&krate, package, version_req, rustc, pre)
+}
 
-    let krate = index
-        .crate_(&package.name)
-        .context("failed finding package")?;
+fn check_versions(
+    krate: &Crate,
+    package: &PackageId,
+    version_req: Option<&VersionReq>,
+    rustc: Option<&Version>,
+    pre: bool,
+) -> Result<RegistryCheck> {
+    let mut yanked = false;
+    let mut candidates = Vec::with_capacity(krate.versions().len());
 
-    let latest = Version::parse(krate.most_recent_version().version())?;
+    for v in krate.versions() {
+        let version = Version::parse(v.version())?;
 
-    if !latest.pre.is_empty() && !pre {
-        return Ok(None);
+        if v.is_yanked() {
+            yanked |= version == package.version;
+        } else if pre || version.pre.is_empty() {
+            candidates.push((version, v.rust_version().and_then(parse_rust_version)));
+        }
     }
 
-    Ok((latest > package.version).then_some(RegistryInfo { version: latest }))
+    let matches_req = |version: &Version| version_req.is_none_or(|req| req.matches(version));
+    let compatible = |rust_version: &Option<Version>| match (rustc, rust_version) {
+        (Some(rustc), Some(rust_version)) => rust_version <= rustc,
+        _ => true,
+    };
+
+    let latest = candidates
+        .iter()
+        .map(|(v, _)| v)
+        .max()
+        .filter(|&v| *v > package.version && !matches_req(v));
+    let highest = candidates
+        .iter()
+        .filter(|(v, _)| matches_req(v))
+        .max_by(|a, b| a.0.cmp(&b.0));
+    let matching = candidates
+        .iter()
+        .filter(|(v, rv)| matches_req(v) && compatible(rv))
+        .map(|(v, _)| v)
+        .max();
+
+    let matching = matching.filter(|&v| *v > package.version);
+    let incompatible = highest
+        .filter(|(v, _)| *v > package.version && matching.is_none_or(|m| v > m))
+        .and_then(|(v, rv)| {
+            Some(IncompatibleVersion {
+                version: v.clone(),
+                rust_version: rv.clone()?,
+            })
+        });
+
+    let update = matching.map(|version| RegistryInfo {
+        version: version.clone(),
+        version_req: version_req.cloned(),
+        latest: latest.cloned(),
+        yanked,
+        incompatible: incompatible.clone(),
+        held_back: false,
+    });
+
+    Ok(RegistryCheck {
+        update,
+        latest: latest.cloned(),
+        incompatible,
+        yanked,
+    })
 }
 
-pub(crate) fn print_updates(updates: &BTreeMap<PackageId, UpdateInfo<RegistryInfo>>) {
+/// Parse the `rust-version` of a crate, which may omit the minor and patch components.
+fn parse_rust_version(value: &str) -> Option<Version> {
+    let mut parts = value.split('.').map(str::parse::<u64>);
+
+    let major = parts.next()?.ok()?;
+    let minor = parts.next().transpose().ok()?.unwrap_or(0);
+    let patch = parts.next().transpose().ok()?.unwrap_or(0);
+
+    Some(Version::new(major, minor, patch))
+}
+
+pub(crate) fn print_updates(updates: &BTreeMap<PackageId, UpdateInfo<RegistryInfo>>, host: &str) {
     if updates.is_empty() {
         println!("no {} crate updates", colors::green("registry"));
     } else {
         let table = updates
             .iter()
-            .map(|(pkg, info)| (pkg.name.as_str(), &pkg.version, &info.extra.version))
+            .map(|(pkg, info)| {
+                (
+                    pkg.name.as_str(),
+                    &pkg.version,
+                    &info.extra,
+                    info.install_info.cross_target(host),
+                )
+            })
             .collect::<RegistryTable>();
 
         println!("\n{table}\n");
     }
 }
 
+/// Print the packages that have newer versions, but no update that can be installed. These are
+/// only informational.
+pub(crate) fn print_outdated(outdated: &BTreeMap<PackageId, OutdatedInfo>) {
+    if outdated.is_empty() {
+        return;
+    }
+
+    let table = outdated
+        .iter()
+        .map(|(pkg, info)| (pkg.name.as_str(), &pkg.version, info))
+        .collect::<OutdatedTable>();
+
+    println!("\n{table}\n");
+}
+
+pub(crate) fn print_yanked(yanked: &BTreeSet<PackageId>) {
+    if yanked.is_empty() {
+        return;
+    }
+
+    let table = yanked
+        .iter()
+        .map(|pkg| (pkg.name.as_str(), &pkg.version))
+        .collect::<YankedTable>();
+
+    println!("\n{table}\n");
+}
+
 pub(crate) fn install_updates(
     updates: impl ExactSizeIterator<Item = (PackageId, UpdateInfo<RegistryInfo>)>,
-    quiet: bool,
-) {
+    options: &InstallOptions,
+) -> Vec<InstallResult> {
     let count = updates.len();
     if count == 0 {
-        return;
+        return Vec::new();
     }
 
-    println!(
+    options.message(format_args!(
         "start installing {} {} updates\n",
         colors::blue(count).bold(),
         colors::green("registry").bold()
-    );
-
-    for (i, (pkg, info)) in updates.enumerate() {
-        println!(
-            "{} updating {} from {} to {}",
-            colors::bold(format_args!("[{}/{}]", i + 1, count)),
-            colors::green(&pkg.name).bold(),
-            colors::blue(pkg.version).bold(),
-            colors::blue(&info.extra.version).bold()
-        );
-
-        if let Err(e) = cargo_install(&pkg.name, &info.extra.version, &info.install_info, quiet) {
-            eprintln!(
-                "\ninstalling {} {}:\n{e}",
-                colors::green(pkg.name).bold(),
-                colors::red("failed").bold()
-            )
-        }
-    }
+    ));
+
+    let jobs = updates
+        .map(|(pkg, info)| InstallJob {
+            change: format!("{} ➞ {}", pkg.version, info.extra.version),
+            from: pkg.version.to_string(),
+            to: Some(info.extra.version.to_string()),
+            message: format!(
+                "updating {} from {} to {}",
+                colors::green(&pkg.name).bold(),
+                colors::blue(&pkg.version).bold(),
+                colors::blue(&info.extra.version).bold()
+            ),
+            cmd: Ok(cargo_install(
+                &pkg,
+                &info.extra,
+                &info.install_info,
+                &options.host,
+            )),
+            package: pkg,
+            info: info.install_info,
+        })
+        .collect();
+
+    install::run(jobs, "installing", options)
 }
 
-fn cargo_install(name: &str, version: &Version, info: &InstallInfo, quiet: bool) -> Result<()> {
+fn cargo_install(
+    package: &PackageId,
+    update: &RegistryInfo,
+    info: &InstallInfo,
+    host: &str,
+) -> Command {
     let mut cmd = Command::new("cargo");
-    cmd.args(["install", name]);
+    cmd.args(["install", &package.name]);
 
+    // Pass on the original version requirement, so cargo keeps recording it for the next update.
+    // If a newer matching version was skipped, which cargo might pick otherwise, the requirement is
+    // capped at the update.
     cmd.arg("--version");
-    cmd.arg(version.to_string());
+    match (&update.version_req, &update.incompatible) {
+        (Some(req), None) => cmd.arg(req.to_string()),
+        (Some(req), Some(_)) => cmd.arg(capped_req(req, &update.version)),
+        (None, _) => cmd.arg(update.version.to_string()),
+    };
+
+    apply_registry_args(&mut cmd, &package.source_id);
+    common::apply_cmd_args(&mut cmd, info, host);
+    cmd
+}
 
-    common::apply_cmd_args(&mut cmd, info);
-    common::run_cmd(cmd, name, quiet)
+/// Combine the version requirement with an upper bound at the given version, so cargo installs
+/// exactly that version, while still recording the original requirement. The bound is dropped
+/// again when reading the recorded requirement, see [`InstallInfo::version_req`].
+pub(crate) fn capped_req(req: &VersionReq, version: &Version) -> String {
+    format!("{req}, <={version}")
+}
+
+/// Point cargo to the same registry that the package was originally installed from. Named
+/// registries are preferred, as cargo can pick up further settings like credentials for them.
+pub(crate) fn apply_registry_args(cmd: &mut Command, source_id: &SourceId) {
+    if source_id.is_crates_io() {
+        return;
+    }
+
+    match &source_id.name {
+        Some(name) => cmd.args(["--registry", name]),
+        None => cmd.args(["--index", source_id.url.as_str()]),
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cargo::CRATES_IO_INDEX;
+
+    #[test]
+    fn rust_version() {
+        assert_eq!(Some(Version::new(1, 70, 0)), parse_rust_version("1.70"));
+        assert_eq!(Some(Version::new(1, 70, 1)), parse_rust_version("1.70.1"));
+        assert_eq!(Some(Version::new(1, 0, 0)), parse_rust_version("1"));
+        assert_eq!(None, parse_rust_version("1.x"));
+    }
+
+    /// Create an index entry with one line for each of the given versions, and whether they were
+    /// yanked.
+    fn krate(versions: &[(&str, bool)]) -> Crate {
+        krate_with_rust_versions(
+            &versions
+                .iter()
+                .map(|&(version, yanked)| (version, yanked, None))
+                .collect::<Vec<_>>(),
+        )
+    }
+
+    /// Like [`krate`], with the `rust-version` of each version.
+    fn krate_with_rust_versions(versions: &[(&str, bool, Option<&str>)]) -> Crate {
+        let lines = versions
+            .iter()
+            .map(|(version, yanked, rust_version)| {
+                format!(
+                    r#"{{"name":"foo","vers":"{version}","deps":[],"features":{{}},"cksum":"{}","yanked":{yanked},"rust_version":{}}}"#,
+                    "0".repeat(64),
+                    rust_version.map_or("null".to_owned(), |rv| format!(r#""{rv}""#))
+                )
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        Crate::from_slice(lines.as_bytes()).unwrap()
+    }
+
+    fn package(version: &str) -> PackageId {
+        serde_json::from_value(format!("foo {version} (registry+{CRATES_IO_INDEX})").into())
+            .unwrap()
+    }
+
+    #[test]
+    fn latest_outside_requirement() {
+        let krate = krate(&[("1.0.0", false), ("1.1.0", false), ("2.0.0", false)]);
+        let req = VersionReq::parse("^1").unwrap();
+
+        let check = check_versions(&krate, &package("1.0.0"), Some(&req), None, false).unwrap();
+        let update = check.update.unwrap();
+        assert_eq!(Version::new(1, 1, 0), update.version);
+        assert_eq!(Some(Version::new(2, 0, 0)), update.latest);
+        assert_eq!(Some(Version::new(2, 0, 0)), check.latest);
+
+        // Still reported, if the newest matching version is installed already.
+        let check = check_versions(&krate, &package("1.1.0"), Some(&req), None, false).unwrap();
+        assert!(check.update.is_none());
+        assert_eq!(Some(Version::new(2, 0, 0)), check.latest);
+
+        let check = check_versions(&krate, &package("1.0.0"), None, None, false).unwrap();
+        assert_eq!(Version::new(2, 0, 0), check.update.unwrap().version);
+        assert_eq!(None, check.latest);
+    }
+
+    #[test]
+    fn skip_yanked_versions() {
+        let krate = krate(&[("1.0.0", true), ("1.1.0", false), ("1.2.0", true)]);
+
+        let check = check_versions(&krate, &package("1.0.0"), None, None, false).unwrap();
+        assert!(check.yanked);
+        let update = check.update.unwrap();
+        assert_eq!(Version::new(1, 1, 0), update.version);
+        assert!(update.yanked);
+
+        let check = check_versions(&krate, &package("1.1.0"), None, None, false).unwrap();
+        assert!(!check.yanked);
+        assert!(check.update.is_none());
+    }
+
+    #[test]
+    fn incompatible_without_update() {
+        let krate = krate_with_rust_versions(&[
+            ("1.0.0", false, Some("1.60")),
+            ("1.1.0", false, Some("1.70")),
+            ("1.2.0", false, Some("1.80")),
+        ]);
+        let rustc = Version::new(1, 70, 0);
+
+        let check = check_versions(&krate, &package("1.0.0"), None, Some(&rustc), false).unwrap();
+        let update = check.update.unwrap();
+        assert_eq!(Version::new(1, 1, 0), update.version);
+        assert_eq!(Version::new(1, 2, 0), update.incompatible.unwrap().version);
+
+        // Still reported, if no newer version can be built at all.
+        let check = check_versions(&krate, &package("1.1.0"), None, Some(&rustc), false).unwrap();
+        assert!(check.update.is_none());
+        let incompatible = check.incompatible.unwrap();
+        assert_eq!(Version::new(1, 2, 0), incompatible.version);
+        assert_eq!(Version::new(1, 80, 0), incompatible.rust_version);
+
+        let check = check_versions(&krate, &package("1.2.0"), None, Some(&rustc), false).unwrap();
+        assert!(check.incompatible.is_none());
+    }
+
+    #[test]
+    fn install_version_req() {
+        let args = |version_req: Option<&str>, incompatible: bool| {
+            let update = RegistryInfo {
+                version: Version::new(1, 1, 0),
+                version_req: version_req.map(|req| VersionReq::parse(req).unwrap()),
+                latest: None,
+                yanked: false,
+                incompatible: incompatible.then(|| IncompatibleVersion {
+                    version: Version::new(1, 2, 0),
+                    rust_version: Version::new(1, 80, 0),
+                }),
+                held_back: false,
+            };
+            let cmd = cargo_install(&package("1.0.0"), &update, &InstallInfo::default(), "");
+            cmd.get_args()
+                .skip_while(|arg| *arg != "--version")
+                .nth(1)
+                .unwrap()
+                .to_string_lossy()
+                .into_owned()
+        };
+
+        assert_eq!("1.1.0", args(None, false));
+        assert_eq!("1.1.0", args(None, true));
+        assert_eq!("^1", args(Some("^1"), false));
+        assert_eq!("^1, <=1.1.0", args(Some("^1"), true));
+    }
 }
@@ -0,0 +1,610 @@
+//! Machine readable reports of the available updates and the installation results, in one of the
+//! JSON based formats that can be selected with `--format`.
+
+use std::{
+    collections::BTreeSet,
+    io::{self, Write},
+    path::Path,
+    time::SystemTime,
+};
+
+use anyhow::Result;
+use rustc_version::VersionMeta;
+use serde::Serialize;
+use serde_json::{Map, Value};
+
+use crate::{
+    cargo::{GitReference, InstallInfo, PackageId, SourceKind},
+    cli::Format,
+    history,
+    models::{
+        GitChanges, GitInfo, GitTarget, IncompatibleVersion, InstallOutcome, InstallResult,
+        OutdatedInfo, PathInfo, RebuildInfo, RegistryInfo, UpdateInfo, UpdateLevel, Updates,
+    },
+};
+
+/// Version of the report schema, that is increased with every breaking change to it.
+const SCHEMA_VERSION: u32 = 1;
+
+/// Report about all found updates, that is completed with the installation results.
+///
+/// In the newline delimited format, each update is written out immediately, whereas the JSON
+/// format writes a single document once the report is finished.
+pub struct Report {
+    format: Format,
+    sections: Map<String, Value>,
+}
+
+impl Report {
+    pub fn new(format: Format, updates: &Updates) -> Result<Self> {
+        let mut report = Self {
+            format,
+            sections: Map::new(),
+        };
+
+        report.add(
+            "registry",
+            updates
+                .registry
+                .iter()
+                .map(|(pkg, info)| registry_entry(pkg, info)),
+        )?;
+        report.add(
+            "outdated",
+            updates
+                .outdated
+                .iter()
+                .map(|(pkg, info)| outdated_entry(pkg, info)),
+        )?;
+        report.add("yanked", updates.yanked.iter().map(Package::new))?;
+        report.add(
+            "git",
+            updates
+                .git
+                .iter()
+                .map(|(pkg, info)| git_entry(pkg, &info.extra, Some(&info.install_info))),
+        )?;
+        report.add(
+            "pinned",
+            updates
+                .pinned
+                .iter()
+                .map(|(pkg, info)| git_entry(pkg, info, None)),
+        )?;
+        report.add(
+            "path",
+            updates.path.iter().map(|(pkg, info)| path_entry(pkg, info)),
+        )?;
+        report.add(
+            "missing",
+            updates.missing.iter().map(|(pkg, path)| MissingEntry {
+                package: Package::new(pkg),
+                path,
+            }),
+        )?;
+        report.add(
+            "rebuild",
+            updates
+                .rebuild
+                .iter()
+                .map(|(pkg, info)| rebuild_entry(pkg, info)),
+        )?;
+        report.add("ignored", updates.ignored.iter().map(Package::new))?;
+
+        Ok(report)
+    }
+
+    /// Complete the report with the results of the installations, which are absent for dry runs.
+    pub fn finish(mut self, results: Option<&[InstallResult]>) -> Result<()> {
+        if let Some(results) = results {
+            self.add("install", results.iter().map(InstallEntry::new))?;
+        }
+
+        if self.format == Format::Json {
+            let mut document = Map::new();
+            document.insert("schema_version".to_owned(), SCHEMA_VERSION.into());
+            document.append(&mut self.sections);
+
+            let mut stdout = io::stdout().lock();
+            serde_json::to_writer_pretty(&mut stdout, &document)?;
+            writeln!(stdout)?;
+        }
+
+        Ok(())
+    }
+
+    fn add<T: Serialize>(&mut self, kind: &str, entries: impl Iterator<Item = T>) -> Result<()> {
+        let mut values = Vec::new();
+
+        for entry in entries {
+            if self.format == Format::Ndjson {
+                #[derive(Serialize)]
+                struct Line<'a, T> {
+                    schema_version: u32,
+                    kind: &'a str,
+                    #[serde(flatten)]
+                    entry: T,
+                }
+
+                let mut stdout = io::stdout().lock();
+                serde_json::to_writer(
+                    &mut stdout,
+                    &Line {
+                        schema_version: SCHEMA_VERSION,
+                        kind,
+                        entry,
+                    },
+                )?;
+                writeln!(stdout)?;
+            } else {
+                values.push(serde_json::to_value(entry)?);
+            }
+        }
+
+        self.sections.insert(kind.to_owned(), values.into());
+        Ok(())
+    }
+}
+
+#[derive(Serialize)]
+struct Package<'a> {
+    name: &'a str,
+    version: String,
+    source: &'static str,
+    url: &'a str,
+}
+
+impl<'a> Package<'a> {
+    fn new(package: &'a PackageId) -> Self {
+        Self {
+            name: &package.name,
+            version: package.version.to_string(),
+            source: history::source_name(package),
+            url: package.source_id.url.as_str(),
+        }
+    }
+}
+
+/// Settings that the package was installed with, and are re-used for the update.
+#[derive(Serialize)]
+struct Install<'a> {
+    bins: &'a BTreeSet<String>,
+    features: &'a BTreeSet<String>,
+    all_features: bool,
+    no_default_features: bool,
+    profile: &'a str,
+    target: Option<&'a str>,
+    root: &'a Path,
+}
+
+impl<'a> Install<'a> {
+    fn new(info: &'a InstallInfo) -> Self {
+        Self {
+            bins: &info.bins,
+            features: &info.features,
+            all_features: info.all_features,
+            no_default_features: info.no_default_features,
+            profile: &info.profile,
+            target: info.target.as_deref(),
+            root: &info.root,
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct RegistryEntry<'a> {
+    #[serde(flatten)]
+    package: Package<'a>,
+    new_version: String,
+    level: UpdateLevel,
+    held_back: bool,
+    version_req: Option<String>,
+    latest: Option<String>,
+    yanked: bool,
+    incompatible: Option<Incompatible>,
+    install: Install<'a>,
+}
+
+#[derive(Serialize)]
+struct Incompatible {
+    version: String,
+    rust_version: String,
+}
+
+impl Incompatible {
+    fn new(incompatible: &IncompatibleVersion) -> Self {
+        Self {
+            version: incompatible.version.to_string(),
+            rust_version: incompatible.rust_version.to_string(),
+        }
+    }
+}
+
+fn registry_entry<'a>(
+    package: &'a PackageId,
+    info: &'a UpdateInfo<RegistryInfo>,
+) -> RegistryEntry<'a> {
+    RegistryEntry {
+        package: Package::new(package),
+        new_version: info.extra.version.to_string(),
+        level: UpdateLevel::of(&package.version, &info.extra.version),
+        held_back: info.extra.held_back,
+        version_req: info.extra.version_req.as_ref().map(ToString::to_string),
+        latest: info.extra.latest.as_ref().map(ToString::to_string),
+        yanked: info.extra.yanked,
+        incompatible: info.extra.incompatible.as_ref().map(Incompatible::new),
+        install: Install::new(&info.install_info),
+    }
+}
+
+#[derive(Serialize)]
+struct OutdatedEntry<'a> {
+    #[serde(flatten)]
+    package: Package<'a>,
+    latest: Option<String>,
+    incompatible: Option<Incompatible>,
+}
+
+fn outdated_entry<'a>(package: &'a PackageId, info: &'a OutdatedInfo) -> OutdatedEntry<'a> {
+    OutdatedEntry {
+        package: Package::new(package),
+        latest: info.latest.as_ref().map(ToString::to_string),
+        incompatible: info.incompatible.as_ref().map(Incompatible::new),
+    }
+}
+
+#[derive(Serialize)]
+struct GitEntry<'a> {
+    #[serde(flatten)]
+    package: Package<'a>,
+    /// Kind of reference that the package was installed from, either `branch`, `tag`, `rev` or
+    /// `default` for the default branch.
+    #[serde(rename = "type")]
+    r#type: &'static str,
+    /// Name of the branch or tag, or the revision, that the package was installed from.
+    #[serde(rename = "ref")]
+    r#ref: Option<&'a str>,
+    /// Branch or tag that the update is installed from, or none for the default branch.
+    to: Option<&'a str>,
+    old_commit: String,
+    new_commit: String,
+    changes: &'a GitChanges,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    install: Option<Install<'a>>,
+}
+
+fn git_entry<'a>(
+    package: &'a PackageId,
+    info: &'a GitInfo,
+    install: Option<&'a InstallInfo>,
+) -> GitEntry<'a> {
+    let (r#type, r#ref) = match &package.source_id.kind {
+        SourceKind::Git(GitReference::Branch(b)) => ("branch", Some(b.as_str())),
+        SourceKind::Git(GitReference::Tag(t)) => ("tag", Some(t.as_str())),
+        SourceKind::Git(GitReference::Rev(r)) => ("rev", Some(r.as_str())),
+        _ => ("default", None),
+    };
+
+    GitEntry {
+        package: Package::new(package),
+        r#type,
+        r#ref,
+        to: match &info.target {
+            GitTarget::Default => None,
+            GitTarget::Branch(name) | GitTarget::Tag(name) => Some(name),
+        },
+        old_commit: info.old_commit.to_string(),
+        new_commit: info.new_commit.to_string(),
+        changes: &info.changes,
+        install: install.map(Install::new),
+    }
+}
+
+#[derive(Serialize)]
+struct PathEntry<'a> {
+    #[serde(flatten)]
+    package: Package<'a>,
+    path: &'a Path,
+    changed_files: usize,
+    /// Modification time of the newest file, in seconds since the Unix epoch.
+    newest_change: u64,
+    git: Option<PathGit<'a>>,
+    install: Install<'a>,
+}
+
+#[derive(Serialize)]
+struct PathGit<'a> {
+    branch: Option<&'a str>,
+    head: String,
+    dirty: bool,
+    ahead: usize,
+}
+
+fn path_entry<'a>(package: &'a PackageId, info: &'a UpdateInfo<PathInfo>) -> PathEntry<'a> {
+    PathEntry {
+        package: Package::new(package),
+        path: &info.extra.path,
+        changed_files: info.extra.changed_files,
+        newest_change: info
+            .extra
+            .newest_change
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .map_or(0, |d| d.as_secs()),
+        git: info.extra.git.as_ref().map(|git| PathGit {
+            branch: git.branch.as_deref(),
+            head: git.head.to_string(),
+            dirty: git.dirty,
+            ahead: git.ahead,
+        }),
+        install: Install::new(&info.install_info),
+    }
+}
+
+#[derive(Serialize)]
+struct MissingEntry<'a> {
+    #[serde(flatten)]
+    package: Package<'a>,
+    path: &'a Path,
+}
+
+#[derive(Serialize)]
+struct RebuildEntry<'a> {
+    #[serde(flatten)]
+    package: Package<'a>,
+    old_rustc: Rustc<'a>,
+    new_rustc: Rustc<'a>,
+    install: Install<'a>,
+}
+
+#[derive(Serialize)]
+struct Rustc<'a> {
+    version: String,
+    commit_hash: Option<&'a str>,
+}
+
+impl<'a> Rustc<'a> {
+    fn new(meta: &'a VersionMeta) -> Self {
+        Self {
+            version: meta.semver.to_string(),
+            commit_hash: meta.commit_hash.as_deref(),
+        }
+    }
+}
+
+fn rebuild_entry<'a>(
+    package: &'a PackageId,
+    info: &'a UpdateInfo<RebuildInfo>,
+) -> RebuildEntry<'a> {
+    RebuildEntry {
+        package: Package::new(package),
+        old_rustc: Rustc::new(&info.extra.old_rustc),
+        new_rustc: Rustc::new(&info.extra.new_rustc),
+        install: Install::new(&info.install_info),
+    }
+}
+
+#[derive(Serialize)]
+struct InstallEntry<'a> {
+    #[serde(flatten)]
+    package: Package<'a>,
+    change: &'a str,
+    outcome: InstallOutcome,
+    /// Time it took to install the update, in seconds.
+    duration: f64,
+    error: Option<&'a str>,
+    details: &'a [String],
+    log: Option<&'a Path>,
+}
+
+impl<'a> InstallEntry<'a> {
+    fn new(result: &'a InstallResult) -> Self {
+        Self {
+            package: Package::new(&result.package),
+            change: &result.change,
+            outcome: result.outcome,
+            duration: result.duration.as_secs_f64(),
+            error: result.error.as_deref(),
+            details: &result.details,
+            log: result.log.as_deref(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use gix::ObjectId;
+    use semver::Version;
+    use serde_json::json;
+
+    use super::*;
+    use crate::models::{GitTarget, PathGitStatus};
+
+    fn package(id: &str) -> PackageId {
+        serde_json::from_value(id.into()).unwrap()
+    }
+
+    fn install_info(bin: &str) -> InstallInfo {
+        serde_json::from_value(json!({
+            "version_req": null,
+            "bins": [bin],
+            "features": [],
+            "all_features": false,
+            "no_default_features": false,
+            "profile": "release",
+            "target": "x86_64-unknown-linux-gnu",
+            "rustc": null,
+        }))
+        .unwrap()
+    }
+
+    fn install(bin: &str) -> Value {
+        json!({
+            "bins": [bin],
+            "features": [],
+            "all_features": false,
+            "no_default_features": false,
+            "profile": "release",
+            "target": "x86_64-unknown-linux-gnu",
+            "root": "",
+        })
+    }
+
+    #[test]
+    fn serialize_updates() {
+        let old = ObjectId::from_hex(b"265df66c0b39ae9ab8a1ad1a4f0a8e9d4e1f1a2b").unwrap();
+        let new = ObjectId::from_hex(b"67beec0d93a8f5e5e3c7b0d3d5b1c4a2e6f7a8b9").unwrap();
+
+        let mut updates = Updates::default();
+        updates.registry.insert(
+            package("foo 1.0.0 (registry+https://github.com/rust-lang/crates.io-index)"),
+            UpdateInfo::new(
+                install_info("foo"),
+                RegistryInfo {
+                    version: Version::new(1, 1, 0),
+                    version_req: None,
+                    latest: None,
+                    yanked: true,
+                    incompatible: None,
+                    held_back: false,
+                },
+            ),
+        );
+        updates.git.insert(
+            package("baz 0.1.0 (git+https://example.com/baz?tag=v0.1.0#265df66c)"),
+            UpdateInfo::new(
+                install_info("baz"),
+                GitInfo {
+                    r#type: "tag v0.1.0 ➞ v0.2.0".to_owned(),
+                    old_commit: old,
+                    new_commit: new,
+                    changes: GitChanges {
+                        commits: 2,
+                        files_changed: 1,
+                        insertions: 3,
+                        deletions: 1,
+                    },
+                    target: GitTarget::Tag("v0.2.0".to_owned()),
+                },
+            ),
+        );
+        updates.pinned.insert(
+            package("qux 0.1.0 (git+https://example.com/qux?rev=265df66#265df66c)"),
+            GitInfo {
+                r#type: "rev 265df66 ➞ HEAD".to_owned(),
+                old_commit: old,
+                new_commit: new,
+                changes: GitChanges::default(),
+                target: GitTarget::Default,
+            },
+        );
+        updates.path.insert(
+            package("bar 0.1.0 (path+file:///tmp/bar)"),
+            UpdateInfo::new(
+                install_info("bar"),
+                PathInfo {
+                    path: "/tmp/bar".into(),
+                    changed_files: 4,
+                    newest_change: SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000),
+                    git: Some(PathGitStatus {
+                        branch: Some("main".to_owned()),
+                        head: new,
+                        dirty: true,
+                        ahead: 1,
+                    }),
+                },
+            ),
+        );
+
+        let mut report = Report::new(Format::Json, &updates).unwrap();
+
+        assert_eq!(
+            json!([{
+                "name": "foo",
+                "version": "1.0.0",
+                "source": "registry",
+                "url": "https://github.com/rust-lang/crates.io-index",
+                "new_version": "1.1.0",
+                "level": "minor",
+                "held_back": false,
+                "version_req": null,
+                "latest": null,
+                "yanked": true,
+                "incompatible": null,
+                "install": install("foo"),
+            }]),
+            report.sections["registry"]
+        );
+        assert_eq!(
+            json!([{
+                "name": "baz",
+                "version": "0.1.0",
+                "source": "git",
+                "url": "https://example.com/baz",
+                "type": "tag",
+                "ref": "v0.1.0",
+                "to": "v0.2.0",
+                "old_commit": old.to_string(),
+                "new_commit": new.to_string(),
+                "changes": {
+                    "commits": 2,
+                    "files_changed": 1,
+                    "insertions": 3,
+                    "deletions": 1,
+                },
+                "install": install("baz"),
+            }]),
+            report.sections["git"]
+        );
+        assert_eq!(
+            json!([{
+                "name": "bar",
+                "version": "0.1.0",
+                "source": "path",
+                "url": "file:///tmp/bar",
+                "path": "/tmp/bar",
+                "changed_files": 4,
+                "newest_change": 1_700_000_000,
+                "git": {
+                    "branch": "main",
+                    "head": new.to_string(),
+                    "dirty": true,
+                    "ahead": 1,
+                },
+                "install": install("bar"),
+            }]),
+            report.sections["path"]
+        );
+        assert_eq!(
+            json!([{
+                "name": "qux",
+                "version": "0.1.0",
+                "source": "git",
+                "url": "https://example.com/qux",
+                "type": "rev",
+                "ref": "265df66",
+                "to": null,
+                "old_commit": old.to_string(),
+                "new_commit": new.to_string(),
+                "changes": {
+                    "commits": 0,
+                    "files_changed": 0,
+                    "insertions": 0,
+                    "deletions": 0,
+                },
+            }]),
+            report.sections["pinned"]
+        );
+
+        let results = [InstallResult::skipped(
+            package("foo 1.0.0 (registry+https://github.com/rust-lang/crates.io-index)"),
+            "1.0.0 ➞ 1.1.0".to_owned(),
+            &anyhow::anyhow!("backup failed"),
+        )];
+        report
+            .add("install", results.iter().map(InstallEntry::new))
+            .unwrap();
+        assert_eq!(json!("skipped"), report.sections["install"][0]["outcome"]);
+    }
+}
@@ -0,0 +1,210 @@
+//! Interactive selection of the updates that should be installed.
+
+use std::{
+    collections::BTreeSet,
+    io::{self, IsTerminal},
+};
+
+use anyhow::Result;
+use dialoguer::{theme::ColorfulTheme, MultiSelect};
+
+use crate::{
+    cargo::PackageId,
+    models::{UpdateLevel, Updates},
+};
+
+/// Present a checklist of all registry, git and path updates as well as rebuilds, and drop any of
+/// them that were deselected.
+///
+/// Cancelling the selection drops all of these updates.
+pub(crate) fn select_updates(updates: &mut Updates) -> Result<()> {
+    let (packages, items): (Vec<_>, Vec<_>) = checklist(updates)
+        .into_iter()
+        .map(|(pkg, label, checked)| (pkg, (label, checked)))
+        .unzip();
+
+    if items.is_empty() {
+        return Ok(());
+    }
+
+    let selection = MultiSelect::with_theme(&ColorfulTheme::default())
+        .with_prompt("Select the updates to install (space to toggle, enter to confirm)")
+        .items_checked(&items)
+        .max_length(20)
+        .interact_opt()?
+        .unwrap_or_default();
+
+    let selected = selection
+        .into_iter()
+        .map(|i| &packages[i])
+        .collect::<BTreeSet<&PackageId>>();
+
+    updates.registry.retain(|pkg, _| selected.contains(pkg));
+    updates.git.retain(|pkg, _| selected.contains(pkg));
+    updates.path.retain(|pkg, _| selected.contains(pkg));
+    updates.rebuild.retain(|pkg, _| selected.contains(pkg));
+
+    Ok(())
+}
+
+/// Build the checklist entries for all selectable updates, as package, label and whether it's
+/// selected by default. Breaking registry updates are unselected by default, all others are
+/// selected.
+fn checklist(updates: &Updates) -> Vec<(PackageId, String, bool)> {
+    let mut items = Vec::new();
+
+    for (pkg, info) in &updates.registry {
+        let breaking = UpdateLevel::of(&pkg.version, &info.extra.version) == UpdateLevel::Major;
+        items.push((
+            pkg.clone(),
+            format!(
+                "{} {} ➞ {}{}",
+                pkg.name,
+                pkg.version,
+                info.extra.version,
+                if breaking { " (major)" } else { "" }
+            ),
+            !breaking,
+        ));
+    }
+
+    for (pkg, info) in &updates.git {
+        items.push((
+            pkg.clone(),
+            format!(
+                "{} {} {} ➞ {} (git)",
+                pkg.name,
+                info.extra.r#type,
+                info.extra.old_commit.to_hex_with_len(7),
+                info.extra.new_commit.to_hex_with_len(7)
+            ),
+            true,
+        ));
+    }
+
+    for (pkg, info) in &updates.path {
+        items.push((
+            pkg.clone(),
+            format!(
+                "{} {} files changed (path)",
+                pkg.name, info.extra.changed_files
+            ),
+            true,
+        ));
+    }
+
+    for (pkg, info) in &updates.rebuild {
+        items.push((
+            pkg.clone(),
+            format!(
+                "{} {} rustc {} ➞ {} (rebuild)",
+                pkg.name, pkg.version, info.extra.old_rustc.semver, info.extra.new_rustc.semver
+            ),
+            true,
+        ));
+    }
+
+    items
+}
+
+/// Whether the interactive selection can be shown, which is only the case if the standard output
+/// is connected to a terminal.
+pub(crate) fn is_available() -> bool {
+    io::stdout().is_terminal()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::SystemTime;
+
+    use semver::Version;
+
+    use super::*;
+    use crate::{
+        cargo::InstallInfo,
+        models::{PathInfo, RebuildInfo, RegistryInfo, UpdateInfo},
+    };
+
+    fn package(id: &str) -> PackageId {
+        serde_json::from_value(id.into()).unwrap()
+    }
+
+    fn registry(version: Version) -> UpdateInfo<RegistryInfo> {
+        UpdateInfo::new(
+            InstallInfo::default(),
+            RegistryInfo {
+                version,
+                version_req: None,
+                latest: None,
+                yanked: false,
+                incompatible: None,
+                held_back: false,
+            },
+        )
+    }
+
+    fn rustc(version: &str) -> rustc_version::VersionMeta {
+        rustc_version::version_meta_for(&format!(
+            "rustc {version}\n\
+             commit-hash: unknown\n\
+             commit-date: unknown\n\
+             host: x86_64-unknown-linux-gnu\n\
+             release: {version}\n"
+        ))
+        .unwrap()
+    }
+
+    #[test]
+    fn build_checklist() {
+        let mut updates = Updates::default();
+        updates.registry.insert(
+            package("a 1.0.0 (registry+https://github.com/rust-lang/crates.io-index)"),
+            registry(Version::new(1, 1, 0)),
+        );
+        updates.registry.insert(
+            package("b 1.0.0 (registry+https://github.com/rust-lang/crates.io-index)"),
+            registry(Version::new(2, 0, 0)),
+        );
+        updates.path.insert(
+            package("c 0.1.0 (path+file:///c)"),
+            UpdateInfo::new(
+                InstallInfo::default(),
+                PathInfo {
+                    path: "/c".into(),
+                    changed_files: 3,
+                    newest_change: SystemTime::UNIX_EPOCH,
+                    git: None,
+                },
+            ),
+        );
+        updates.rebuild.insert(
+            package("d 0.2.0 (registry+https://github.com/rust-lang/crates.io-index)"),
+            UpdateInfo::new(
+                InstallInfo::default(),
+                RebuildInfo {
+                    old_rustc: rustc("1.80.0"),
+                    new_rustc: rustc("1.82.0"),
+                },
+            ),
+        );
+
+        let items = checklist(&updates)
+            .into_iter()
+            .map(|(pkg, label, checked)| (pkg.name, label, checked))
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            vec![
+                ("a".to_owned(), "a 1.0.0 ➞ 1.1.0".to_owned(), true),
+                ("b".to_owned(), "b 1.0.0 ➞ 2.0.0 (major)".to_owned(), false),
+                ("c".to_owned(), "c 3 files changed (path)".to_owned(), true),
+                (
+                    "d".to_owned(),
+                    "d 0.2.0 rustc 1.80.0 ➞ 1.82.0 (rebuild)".to_owned(),
+                    true
+                ),
+            ],
+            items
+        );
+    }
+}
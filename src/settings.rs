@@ -0,0 +1,404 @@
+//! Settings of this plugin, that control how each of the installed crates is updated.
+//!
+//! The settings are read from the user's configuration directory at
+//! `$XDG_CONFIG_HOME/cargo-bup/config.toml` and from `$CARGO_HOME/bup.toml`, where the latter takes
+//! precedence. For example:
+//!
+//! ```toml
+//! # Defaults for all crates.
+//! git = true
+//! only = "minor"
+//! keep-backups = 5
+//! smoke-test-args = ["--help"]
+//!
+//! [cargo]
+//! locked = true
+//!
+//! [packages.cargo-nextest]
+//! ignore = true
+//!
+//! [packages.ripgrep]
+//! pin = "^13"
+//! pre = true
+//!
+//! [packages.cargo-edit]
+//! respect-req = true
+//! ```
+
+use std::{
+    collections::BTreeMap,
+    env, fs,
+    io::ErrorKind,
+    path::{Path, PathBuf},
+};
+
+use anstream::eprintln;
+use anyhow::{Context, Result};
+use semver::VersionReq;
+use serde::Deserialize;
+
+use crate::{colors, models::UpdateLevel};
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct Settings {
+    /// Defaults that apply to all crates.
+    #[serde(flatten)]
+    defaults: Rules,
+    /// Number of backups to keep for each crate, where zero disables backups.
+    keep_backups: Option<usize>,
+    /// Check that updated binaries start up, like `--smoke-test`.
+    smoke_test: Option<bool>,
+    /// Arguments that the binaries are run with for the smoke test.
+    smoke_test_args: Option<Vec<String>>,
+    /// Arguments for every `cargo install` invocation.
+    #[serde(default)]
+    cargo: CargoSettings,
+    /// Rules for single crates, keyed by their name.
+    #[serde(default)]
+    packages: BTreeMap<String, PackageRules>,
+}
+
+/// Defaults for the arguments that are passed on to `cargo install`, which are combined with the
+/// ones given on the command line.
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct CargoSettings {
+    /// Like `--locked`.
+    pub locked: Option<bool>,
+    /// Like `--offline`.
+    pub offline: Option<bool>,
+    /// Like `--force`.
+    pub force: Option<bool>,
+    /// Like `--build-jobs`.
+    pub build_jobs: Option<u32>,
+    /// Like `--config`.
+    #[serde(default)]
+    pub config: Vec<String>,
+    /// Like `--target-dir`.
+    pub target_dir: Option<PathBuf>,
+    /// Any further arguments, like the ones given after `--`.
+    #[serde(default)]
+    pub args: Vec<String>,
+    /// Any unknown keys, that are reported as likely typos.
+    #[serde(flatten)]
+    unknown: BTreeMap<String, toml::Value>,
+}
+
+impl CargoSettings {
+    fn merge(&mut self, other: Self) {
+        self.locked = other.locked.or(self.locked);
+        self.offline = other.offline.or(self.offline);
+        self.force = other.force.or(self.force);
+        self.build_jobs = other.build_jobs.or(self.build_jobs);
+        self.config.extend(other.config);
+        self.target_dir = other.target_dir.or(self.target_dir.take());
+        self.args.extend(other.args);
+    }
+}
+
+/// Rules that can be set either for all or single crates. Any unset value falls back to the
+/// defaults, and eventually to the command line arguments.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+struct Rules {
+    /// Check for updates of crates installed from Git repositories, like `--git`.
+    git: Option<bool>,
+    /// Check for updates of crates installed from local paths, like `--path`.
+    path: Option<bool>,
+    /// Include pre-releases in updates, like `--pre`.
+    pre: Option<bool>,
+    /// Move crates installed from a Git tag to newer tags, like `--follow-tags`.
+    follow_tags: Option<bool>,
+    /// Highest level of registry updates to install, like `--only`.
+    only: Option<UpdateLevel>,
+    /// Only offer updates that match the recorded version requirement, like `--respect-req`.
+    respect_req: Option<bool>,
+    /// Any unknown keys, that are reported as likely typos.
+    #[serde(flatten)]
+    unknown: BTreeMap<String, toml::Value>,
+}
+
+impl Rules {
+    fn merge(self, other: Self) -> Self {
+        Self {
+            git: other.git.or(self.git),
+            path: other.path.or(self.path),
+            pre: other.pre.or(self.pre),
+            follow_tags: other.follow_tags.or(self.follow_tags),
+            only: other.only.or(self.only),
+            respect_req: other.respect_req.or(self.respect_req),
+            unknown: BTreeMap::new(),
+        }
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+struct PackageRules {
+    /// Leave the crate alone, never checking or installing any updates for it.
+    ignore: Option<bool>,
+    /// Only offer updates that match this version requirement.
+    pin: Option<VersionReq>,
+    #[serde(flatten)]
+    rules: Rules,
+}
+
+/// The effective settings for a single crate.
+pub struct PackageSettings<'a> {
+    pub ignore: bool,
+    pub pin: Option<&'a VersionReq>,
+    pub git: bool,
+    pub path: bool,
+    pub pre: bool,
+    pub follow_tags: bool,
+    pub only: Option<UpdateLevel>,
+    pub respect_req: bool,
+}
+
+impl Settings {
+    /// Load the settings from the user's configuration directory and cargo's home directory,
+    /// merging them together. Missing files result in the default settings.
+    pub fn load() -> Result<Self> {
+        let mut settings = Self::default();
+
+        if let Some(dir) = config_dir() {
+            settings.merge(Self::load_file(&dir.join("cargo-bup/config.toml"))?);
+        }
+
+        settings.merge(Self::load_file(&home::cargo_home()?.join("bup.toml"))?);
+
+        Ok(settings)
+    }
+
+    fn load_file(path: &Path) -> Result<Self> {
+        let content = match fs::read_to_string(path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(Self::default()),
+            Err(e) => return Err(e.into()),
+        };
+
+        let settings = toml::from_str::<Self>(&content)
+            .with_context(|| format!("failed parsing `{}`", path.display()))?;
+
+        for key in settings.unknown_keys() {
+            eprintln!(
+                "{} unknown setting `{key}` in `{}`",
+                colors::yellow("warning:").bold(),
+                path.display()
+            );
+        }
+
+        Ok(settings)
+    }
+
+    /// Keys that don't belong to any setting, like misspelled ones, which would be silently
+    /// ignored otherwise.
+    fn unknown_keys(&self) -> Vec<String> {
+        let defaults = self.defaults.unknown.keys().cloned();
+        let cargo = self.cargo.unknown.keys().map(|key| format!("cargo.{key}"));
+        let packages = self.packages.iter().flat_map(|(name, package)| {
+            package
+                .rules
+                .unknown
+                .keys()
+                .map(move |key| format!("packages.{name}.{key}"))
+        });
+
+        defaults.chain(cargo).chain(packages).collect()
+    }
+
+    /// Merge the other settings into these, with the other ones taking precedence.
+    fn merge(&mut self, other: Self) {
+        self.defaults = std::mem::take(&mut self.defaults).merge(other.defaults);
+        self.keep_backups = other.keep_backups.or(self.keep_backups);
+        self.smoke_test = other.smoke_test.or(self.smoke_test);
+        self.smoke_test_args = other.smoke_test_args.or(self.smoke_test_args.take());
+        self.cargo.merge(other.cargo);
+
+        for (name, other) in other.packages {
+            let package = self.packages.entry(name).or_default();
+            package.ignore = other.ignore.or(package.ignore);
+            package.pin = other.pin.or(package.pin.take());
+            package.rules = std::mem::take(&mut package.rules).merge(other.rules);
+        }
+    }
+
+    /// Number of backups to keep for each crate, defaulting to three.
+    pub fn keep_backups(&self) -> usize {
+        self.keep_backups.unwrap_or(3)
+    }
+
+    pub fn smoke_test(&self) -> bool {
+        self.smoke_test.unwrap_or_default()
+    }
+
+    /// Arguments for the smoke test, defaulting to `--version`.
+    pub fn smoke_test_args(&self) -> Vec<String> {
+        self.smoke_test_args
+            .clone()
+            .unwrap_or_else(|| vec!["--version".to_owned()])
+    }
+
+    pub fn cargo(&self) -> &CargoSettings {
+        &self.cargo
+    }
+
+    /// Get the settings for a single crate, falling back to the defaults for any unset values.
+    pub fn package(&self, name: &str) -> PackageSettings<'_> {
+        self.resolve(self.packages.get(name))
+    }
+
+    /// Whether the given check is enabled for any of the crates, or by default.
+    pub fn any(&self, f: impl Fn(&PackageSettings<'_>) -> bool) -> bool {
+        f(&self.resolve(None)) || self.packages.values().any(|p| f(&self.resolve(Some(p))))
+    }
+
+    fn resolve<'a>(&'a self, package: Option<&'a PackageRules>) -> PackageSettings<'a> {
+        let rules = match package {
+            Some(package) => self.defaults.clone().merge(package.rules.clone()),
+            None => self.defaults.clone(),
+        };
+
+        PackageSettings {
+            ignore: package.and_then(|p| p.ignore).unwrap_or_default(),
+            pin: package.and_then(|p| p.pin.as_ref()),
+            git: rules.git.unwrap_or_default(),
+            path: rules.path.unwrap_or_default(),
+            pre: rules.pre.unwrap_or_default(),
+            follow_tags: rules.follow_tags.unwrap_or_default(),
+            only: rules.only,
+            respect_req: rules.respect_req.unwrap_or_default(),
+        }
+    }
+}
+
+/// Locate the user's configuration directory, following the XDG base directory specification.
+fn config_dir() -> Option<PathBuf> {
+    env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .filter(|dir| dir.is_absolute())
+        .or_else(|| home::home_dir().map(|home| home.join(".config")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn merge_settings() {
+        let mut settings = toml::from_str::<Settings>(
+            r#"
+            git = true
+
+            [packages.foo]
+            ignore = true
+
+            [packages.bar]
+            pin = "^1"
+            pre = true
+
+            [packages.baz]
+            ignore = true
+            "#,
+        )
+        .unwrap();
+
+        settings.merge(
+            toml::from_str(
+                r#"
+                [packages.bar]
+                git = false
+                pin = "^2"
+
+                [packages.baz]
+                ignore = false
+                "#,
+            )
+            .unwrap(),
+        );
+
+        let foo = settings.package("foo");
+        assert!(foo.ignore);
+        assert!(foo.git);
+
+        let bar = settings.package("bar");
+        assert!(!bar.ignore);
+        assert!(!bar.git);
+        assert!(bar.pre);
+        assert_eq!(Some(&VersionReq::parse("^2").unwrap()), bar.pin);
+
+        // A later file can lift an ignore again.
+        assert!(!settings.package("baz").ignore);
+
+        let other = settings.package("other");
+        assert!(!other.ignore);
+        assert!(other.git);
+        assert!(!other.pre);
+        assert_eq!(None, other.pin);
+    }
+
+    #[test]
+    fn merge_cargo_settings() {
+        let mut settings = toml::from_str::<Settings>(
+            r#"
+            [cargo]
+            locked = true
+            build-jobs = 2
+            config = ["net.git-fetch-with-cli=true"]
+            target-dir = "/tmp/a"
+            args = ["--debug"]
+            "#,
+        )
+        .unwrap();
+
+        settings.merge(
+            toml::from_str(
+                r#"
+                [cargo]
+                locked = false
+                config = ["build.rustflags=[]"]
+                args = ["--timings"]
+                "#,
+            )
+            .unwrap(),
+        );
+
+        let cargo = settings.cargo();
+        assert_eq!(Some(false), cargo.locked);
+        assert_eq!(None, cargo.offline);
+        assert_eq!(Some(2), cargo.build_jobs);
+        assert_eq!(
+            vec!["net.git-fetch-with-cli=true", "build.rustflags=[]"],
+            cargo.config
+        );
+        assert_eq!(Some(Path::new("/tmp/a")), cargo.target_dir.as_deref());
+        assert_eq!(vec!["--debug", "--timings"], cargo.args);
+    }
+
+    #[test]
+    fn unknown_keys() {
+        let settings = toml::from_str::<Settings>(
+            r#"
+            git = true
+            keep-backup = 2
+
+            [cargo]
+            locked = true
+            lock = true
+
+            [packages.foo]
+            ignor = true
+            pin = "^1"
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            vec!["keep-backup", "cargo.lock", "packages.foo.ignor"],
+            settings.unknown_keys()
+        );
+        assert!(settings.package("foo").git);
+        assert_eq!(Some(true), settings.cargo().locked);
+    }
+}
@@ -1,12 +1,17 @@
 //! Printing of data (to the terminal) in a table-ish formatting.
 
-use std::fmt::{self, Display};
+use std::{
+    fmt::{self, Display},
+    time::{Duration, SystemTime},
+};
 
 use anstyle::AnsiColor;
 use gix::ObjectId;
+use rustc_version::VersionMeta;
 use semver::Version;
 use tabled::{
     settings::{
+        disable::Disable,
         object::{Columns, Rows, Segment},
         style::{Border, HorizontalLine, Style},
         Alignment, Modify, Padding, Panel,
@@ -14,27 +19,67 @@ use tabled::{
     Table, Tabled,
 };
 
-use crate::{colors, models::GitInfo};
+use crate::{
+    colors,
+    history::Entry,
+    models::{
+        GitInfo, IncompatibleVersion, InstallOutcome, InstallResult, OutdatedInfo, PathInfo,
+        RebuildInfo, RegistryInfo, UpdateLevel,
+    },
+};
 
 /// The registry table prints updates for crates that come directly from the a crate registry.
 #[derive(Default)]
 pub struct RegistryTable(Vec<RegistryRow>);
 
 impl RegistryTable {
-    pub fn add(&mut self, name: &str, current: &Version, latest: &Version) {
+    pub fn add(
+        &mut self,
+        name: &str,
+        current: &Version,
+        info: &RegistryInfo,
+        target: Option<&str>,
+    ) {
         self.0.push(RegistryRow {
             name: name.to_owned(),
-            current: current.to_string(),
-            latest: ColorizedVersion::new(current, latest).to_string(),
+            current: if info.yanked {
+                colors::red(current).bold().to_string()
+            } else {
+                current.to_string()
+            },
+            latest: if info.held_back {
+                format!(
+                    "{} {}",
+                    ColorizedVersion::new(current, &info.version),
+                    colors::dimmed("(held back)")
+                )
+            } else {
+                ColorizedVersion::new(current, &info.version).to_string()
+            },
+            overall: info
+                .latest
+                .as_ref()
+                .map(|latest| ColorizedVersion::new(current, latest).to_string())
+                .unwrap_or_default(),
+            incompatible: info
+                .incompatible
+                .as_ref()
+                .map(|incompatible| display_incompatible(current, incompatible))
+                .unwrap_or_default(),
+            target: target.unwrap_or_default().to_owned(),
         });
     }
 }
 
-impl<'a> FromIterator<(&'a str, &'a Version, &'a Version)> for RegistryTable {
-    fn from_iter<T: IntoIterator<Item = (&'a str, &'a Version, &'a Version)>>(iter: T) -> Self {
+impl<'a> FromIterator<(&'a str, &'a Version, &'a RegistryInfo, Option<&'a str>)> for RegistryTable {
+    fn from_iter<
+        T: IntoIterator<Item = (&'a str, &'a Version, &'a RegistryInfo, Option<&'a str>)>,
+    >(
+        iter: T,
+    ) -> Self {
         let mut table = Self::default();
-        for (name, current, latest) in iter {
-            table.add(name, current, latest);
+        for (name, current, info, target) in iter {
+            table.add(name, current, info, target);
         }
 
         table
@@ -43,10 +88,25 @@ impl<'a> FromIterator<(&'a str, &'a Version, &'a Version)> for RegistryTable {
 
 impl Display for RegistryTable {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut table = Table::new(&self.0);
+
+        // Only show the informational columns if any of the updates was held back, either by its
+        // version requirement or the required Rust version. Removed from the back, to keep the
+        // column indices valid. The same goes for the target of cross-compiled binaries.
+        if self.0.iter().all(|row| row.target.is_empty()) {
+            table.with(Disable::column(Columns::single(5)));
+        }
+        if self.0.iter().all(|row| row.incompatible.is_empty()) {
+            table.with(Disable::column(Columns::single(4)));
+        }
+        if self.0.iter().all(|row| row.overall.is_empty()) {
+            table.with(Disable::column(Columns::single(3)));
+        }
+
         writeln!(
             f,
             "{}",
-            Table::new(&self.0)
+            table
                 // Add color legend as header
                 .with(Panel::header(format!(
                     "{} major · {} minor · {} patch",
@@ -83,6 +143,253 @@ struct RegistryRow {
     name: String,
     current: String,
     latest: String,
+    #[tabled(rename = "Latest Overall")]
+    overall: String,
+    #[tabled(rename = "Incompatible")]
+    incompatible: String,
+    target: String,
+}
+
+/// The outdated table lists registry crates without any installable update, that still have newer
+/// versions outside of their version requirement or for a newer Rust toolchain.
+#[derive(Default)]
+pub struct OutdatedTable(Vec<OutdatedRow>);
+
+impl OutdatedTable {
+    pub fn add(&mut self, name: &str, current: &Version, info: &OutdatedInfo) {
+        self.0.push(OutdatedRow {
+            name: name.to_owned(),
+            current: current.to_string(),
+            overall: info
+                .latest
+                .as_ref()
+                .map(|latest| ColorizedVersion::new(current, latest).to_string())
+                .unwrap_or_default(),
+            incompatible: info
+                .incompatible
+                .as_ref()
+                .map(|incompatible| display_incompatible(current, incompatible))
+                .unwrap_or_default(),
+        });
+    }
+}
+
+impl<'a> FromIterator<(&'a str, &'a Version, &'a OutdatedInfo)> for OutdatedTable {
+    fn from_iter<T: IntoIterator<Item = (&'a str, &'a Version, &'a OutdatedInfo)>>(
+        iter: T,
+    ) -> Self {
+        let mut table = Self::default();
+        for (name, current, info) in iter {
+            table.add(name, current, info);
+        }
+
+        table
+    }
+}
+
+impl Display for OutdatedTable {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut table = Table::new(&self.0);
+
+        // Only show the columns for the reasons that apply to any of the crates. Removed from the
+        // back, to keep the column indices valid.
+        if self.0.iter().all(|row| row.incompatible.is_empty()) {
+            table.with(Disable::column(Columns::single(3)));
+        }
+        if self.0.iter().all(|row| row.overall.is_empty()) {
+            table.with(Disable::column(Columns::single(2)));
+        }
+
+        writeln!(
+            f,
+            "{}",
+            table
+                .with(Panel::header(
+                    colors::yellow("Newer versions that can't be installed")
+                        .bold()
+                        .to_string()
+                ))
+                .with(
+                    Modify::new(Rows::first())
+                        .with(Alignment::center())
+                        .with(Padding::new(1, 1, 0, 1))
+                )
+                // Draw straight line under the headers
+                .with(
+                    Style::blank().horizontals([(2, HorizontalLine::new('─').intersection('─'))])
+                )
+        )
+    }
+}
+
+/// Single row for the [`OutdatedTable`], that can be used with [`tabled`].
+#[derive(Tabled)]
+#[tabled(rename_all = "PascalCase")]
+struct OutdatedRow {
+    name: String,
+    current: String,
+    #[tabled(rename = "Latest Overall")]
+    overall: String,
+    incompatible: String,
+}
+
+/// Format a version that requires a newer Rust toolchain, together with the required version.
+fn display_incompatible(current: &Version, incompatible: &IncompatibleVersion) -> String {
+    format!(
+        "{} {}",
+        ColorizedVersion::new(current, &incompatible.version),
+        colors::dimmed(format_args!("needs rustc {}", incompatible.rust_version))
+    )
+}
+
+/// The yanked table lists installed binaries, whose exact version was yanked from its registry.
+#[derive(Default)]
+pub struct YankedTable(Vec<YankedRow>);
+
+impl YankedTable {
+    pub fn add(&mut self, name: &str, version: &Version) {
+        self.0.push(YankedRow {
+            name: name.to_owned(),
+            version: colors::red(version).bold().to_string(),
+        });
+    }
+}
+
+impl<'a> FromIterator<(&'a str, &'a Version)> for YankedTable {
+    fn from_iter<T: IntoIterator<Item = (&'a str, &'a Version)>>(iter: T) -> Self {
+        let mut table = Self::default();
+        for (name, version) in iter {
+            table.add(name, version);
+        }
+
+        table
+    }
+}
+
+impl Display for YankedTable {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{}",
+            Table::new(&self.0)
+                .with(Panel::header(
+                    colors::red("Installed versions that were yanked")
+                        .bold()
+                        .to_string()
+                ))
+                .with(
+                    Modify::new(Rows::first())
+                        .with(Alignment::center())
+                        .with(Padding::new(1, 1, 0, 1))
+                )
+                // Draw straight line under the headers
+                .with(
+                    Style::blank().horizontals([(2, HorizontalLine::new('─').intersection('─'))])
+                )
+        )
+    }
+}
+
+/// Single row for the [`YankedTable`], that can be used with [`tabled`].
+#[derive(Tabled)]
+#[tabled(rename_all = "PascalCase")]
+struct YankedRow {
+    name: String,
+    version: String,
+}
+
+/// The rebuild table lists binaries that are re-installed, as they were built with an outdated
+/// Rust toolchain.
+#[derive(Default)]
+pub struct RebuildTable(Vec<RebuildRow>);
+
+impl RebuildTable {
+    pub fn add(&mut self, name: &str, version: &Version, info: &RebuildInfo, target: Option<&str>) {
+        self.0.push(RebuildRow {
+            name: name.to_owned(),
+            version: version.to_string(),
+            old_rustc: display_rustc(&info.old_rustc),
+            new_rustc: display_rustc(&info.new_rustc),
+            target: target.unwrap_or_default().to_owned(),
+        });
+    }
+}
+
+impl<'a> FromIterator<(&'a str, &'a Version, &'a RebuildInfo, Option<&'a str>)> for RebuildTable {
+    fn from_iter<
+        T: IntoIterator<Item = (&'a str, &'a Version, &'a RebuildInfo, Option<&'a str>)>,
+    >(
+        iter: T,
+    ) -> Self {
+        let mut table = Self::default();
+        for (name, version, info, target) in iter {
+            table.add(name, version, info, target);
+        }
+
+        table
+    }
+}
+
+impl Display for RebuildTable {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut table = Table::new(&self.0);
+
+        // Only show the target if any of the binaries was cross-compiled.
+        if self.0.iter().all(|row| row.target.is_empty()) {
+            table.with(Disable::column(Columns::single(4)));
+        }
+
+        writeln!(
+            f,
+            "{}",
+            table
+                .with(Panel::header(
+                    colors::green("Rebuilds with the current toolchain")
+                        .bold()
+                        .to_string()
+                ))
+                .with(
+                    Modify::new(Rows::first())
+                        .with(Alignment::center())
+                        .with(Padding::new(1, 1, 0, 1))
+                )
+                // Draw straight line under the headers
+                .with(Style::blank().horizontals([(2, HorizontalLine::new('─').intersection('─'))]))
+                // Draw arrow between old and new compiler
+                .with(Modify::new(Segment::new(2.., 2..=2)).with(Border::new().set_right('➞')))
+                // Add spacing between old and new compiler
+                .with(Modify::new(Columns::single(2)).with(Padding::new(1, 2, 0, 0)))
+                .with(Modify::new(Columns::single(3)).with(Padding::new(2, 1, 0, 0)))
+        )
+    }
+}
+
+/// Single row for the [`RebuildTable`], that can be used with [`tabled`].
+#[derive(Tabled)]
+struct RebuildRow {
+    #[tabled(rename = "Name")]
+    name: String,
+    #[tabled(rename = "Version")]
+    version: String,
+    #[tabled(rename = "Old rustc")]
+    old_rustc: String,
+    #[tabled(rename = "New rustc")]
+    new_rustc: String,
+    #[tabled(rename = "Target")]
+    target: String,
+}
+
+/// Format a compiler version, including the commit hash if available, as toolchains of the same
+/// version can still differ (like nightly releases).
+fn display_rustc(value: &VersionMeta) -> String {
+    match &value.commit_hash {
+        Some(hash) => format!(
+            "{} {}",
+            colors::blue(&value.semver),
+            colors::dimmed(format_args!("({hash:.9})"))
+        ),
+        None => colors::blue(&value.semver).to_string(),
+    }
 }
 
 /// A SemVer version that is a colored, based on how much two versions differ from one another. The
@@ -97,14 +404,11 @@ impl<'a> ColorizedVersion<'a> {
         Self { current, latest }
     }
 
-    fn select_colors(&self) -> [AnsiColor; 3] {
-        let major = (self.current.major, self.latest.major);
-        let minor = (self.current.minor, self.latest.minor);
-
-        match (major, minor) {
-            ((0, 0), (0, 0)) => [AnsiColor::Yellow; 3],
-            ((0, 0), _) => [AnsiColor::Yellow, AnsiColor::Yellow, AnsiColor::Green],
-            _ => [AnsiColor::Yellow, AnsiColor::Green, AnsiColor::Blue],
+    fn color(&self) -> AnsiColor {
+        match UpdateLevel::of(self.current, self.latest) {
+            UpdateLevel::Major => AnsiColor::Yellow,
+            UpdateLevel::Minor => AnsiColor::Green,
+            UpdateLevel::Patch => AnsiColor::Blue,
         }
     }
 }
@@ -115,26 +419,22 @@ impl<'a> Display for ColorizedVersion<'a> {
         let minor = self.latest.minor;
         let patch = self.latest.patch;
 
-        let colors = self.select_colors();
+        let color = self.color();
 
         if self.current.major != self.latest.major {
             write!(
                 f,
                 "{}",
-                colors::Styled::fg(format_args!("{major}.{minor}.{patch}"), colors[0])
+                colors::Styled::fg(format_args!("{major}.{minor}.{patch}"), color)
             )?;
         } else if self.current.minor != self.latest.minor {
             write!(
                 f,
                 "{major}.{}",
-                colors::Styled::fg(format_args!("{minor}.{patch}"), colors[1])
+                colors::Styled::fg(format_args!("{minor}.{patch}"), color)
             )?;
         } else {
-            write!(
-                f,
-                "{major}.{minor}.{}",
-                colors::Styled::fg(patch, colors[2])
-            )?;
+            write!(f, "{major}.{minor}.{}", colors::Styled::fg(patch, color))?;
         }
 
         if !self.latest.pre.is_empty() {
@@ -154,21 +454,49 @@ impl<'a> Display for ColorizedVersion<'a> {
 pub struct GitTable<'a>(Vec<GitRow<'a>>);
 
 impl<'a> GitTable<'a> {
+    pub fn add(&mut self, name: &'a str, info: &'a GitInfo, target: Option<&'a str>) {
+        self.0.push(GitRow::new(name, info, target));
+    }
+}
+
+impl<'a> FromIterator<(&'a str, &'a GitInfo, Option<&'a str>)> for GitTable<'a> {
+    fn from_iter<T: IntoIterator<Item = (&'a str, &'a GitInfo, Option<&'a str>)>>(iter: T) -> Self {
+        let mut table = Self::default();
+        for (name, info, target) in iter {
+            table.add(name, info, target);
+        }
+
+        table
+    }
+}
+
+impl<'a> Display for GitTable<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{}",
+            git_table(
+                &self.0,
+                colors::green(format_args!("Updates from {}", "git"))
+                    .bold()
+                    .to_string()
+            )
+        )
+    }
+}
+
+/// The pinned table lists crates installed from a fixed Git revision, together with the changes
+/// on the remote default branch, that they are missing out on.
+#[derive(Default)]
+pub struct PinnedTable<'a>(Vec<GitRow<'a>>);
+
+impl<'a> PinnedTable<'a> {
     pub fn add(&mut self, name: &'a str, info: &'a GitInfo) {
-        self.0.push(GitRow {
-            name,
-            r#type: &info.r#type,
-            old_commit: info.old_commit,
-            new_commit: info.new_commit,
-            commits: info.changes.commits,
-            files_changed: info.changes.files_changed,
-            insertions: info.changes.insertions,
-            deletions: info.changes.deletions,
-        });
+        self.0.push(GitRow::new(name, info, None));
     }
 }
 
-impl<'a> FromIterator<(&'a str, &'a GitInfo)> for GitTable<'a> {
+impl<'a> FromIterator<(&'a str, &'a GitInfo)> for PinnedTable<'a> {
     fn from_iter<T: IntoIterator<Item = (&'a str, &'a GitInfo)>>(iter: T) -> Self {
         let mut table = Self::default();
         for (name, info) in iter {
@@ -179,14 +507,135 @@ impl<'a> FromIterator<(&'a str, &'a GitInfo)> for GitTable<'a> {
     }
 }
 
-impl<'a> Display for GitTable<'a> {
+impl<'a> Display for PinnedTable<'a> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         writeln!(
             f,
             "{}",
-            Table::new(&self.0)
+            git_table(
+                &self.0,
+                colors::yellow("Pinned git revisions behind upstream")
+                    .bold()
+                    .to_string()
+            )
+        )
+    }
+}
+
+/// Create the layout shared by the [`GitTable`] and [`PinnedTable`], with the given header.
+fn git_table(rows: &[GitRow<'_>], header: String) -> Table {
+    let mut table = Table::new(rows);
+
+    // Only show the target if any of the binaries was cross-compiled.
+    if rows.iter().all(|row| row.target.is_empty()) {
+        table.with(Disable::column(Columns::single(8)));
+    }
+
+    table
+        .with(Panel::header(header))
+        .with(
+            Modify::new(Rows::first())
+                .with(Alignment::center())
+                .with(Padding::new(1, 1, 0, 1)),
+        )
+        // Draw strait line under the headers
+        .with(Style::blank().horizontals([(2, HorizontalLine::new('─').intersection('─'))]))
+        // Draw arrow between old and new commit
+        .with(Modify::new(Segment::new(2.., 2..=2)).with(Border::new().set_right('➞')))
+        // Add spacing between old and new commit
+        .with(Modify::new(Columns::single(2)).with(Padding::new(1, 2, 0, 0)))
+        .with(Modify::new(Columns::single(3)).with(Padding::new(2, 1, 0, 0)))
+        // Align commit details and reduce padding
+        .with(
+            Modify::new(Segment::new(2.., 4..))
+                .with(Alignment::right())
+                .with(Padding::zero()),
+        )
+        .with(Modify::new(Columns::single(4)).with(Padding::new(1, 0, 0, 0)))
+        .with(Modify::new(Columns::single(7)).with(Padding::new(0, 1, 0, 0)))
+        .with(
+            Modify::new(Segment::new(2.., 8..))
+                .with(Alignment::left())
+                .with(Padding::new(2, 1, 0, 0)),
+        );
+
+    table
+}
+
+/// The path table prints updates for crates that were installed from local directories, together
+/// with the state of their Git worktree.
+#[derive(Default)]
+pub struct PathTable(Vec<PathRow>);
+
+impl PathTable {
+    pub fn add(&mut self, name: &str, info: &PathInfo, target: Option<&str>) {
+        let git = info.git.as_ref();
+
+        self.0.push(PathRow {
+            name: name.to_owned(),
+            directory: info.path.display().to_string(),
+            branch: git
+                .map(|git| match &git.branch {
+                    Some(branch) => colors::blue(branch).to_string(),
+                    None => colors::dimmed("detached").to_string(),
+                })
+                .unwrap_or_default(),
+            commit: git.map(|git| display_commit(&git.head)).unwrap_or_default(),
+            worktree: git
+                .map(|git| {
+                    if git.dirty {
+                        colors::red("dirty").to_string()
+                    } else {
+                        colors::green("clean").to_string()
+                    }
+                })
+                .unwrap_or_default(),
+            ahead: git
+                .map(|git| display_commit_count(&git.ahead))
+                .unwrap_or_default(),
+            changes: format!(
+                "{} files changed, last {}",
+                colors::white(info.changed_files),
+                colors::yellow(display_age(info.newest_change))
+            ),
+            target: target.unwrap_or_default().to_owned(),
+        });
+    }
+}
+
+impl<'a> FromIterator<(&'a str, &'a PathInfo, Option<&'a str>)> for PathTable {
+    fn from_iter<T: IntoIterator<Item = (&'a str, &'a PathInfo, Option<&'a str>)>>(
+        iter: T,
+    ) -> Self {
+        let mut table = Self::default();
+        for (name, info, target) in iter {
+            table.add(name, info, target);
+        }
+
+        table
+    }
+}
+
+impl Display for PathTable {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut table = Table::new(&self.0);
+
+        // Only show the target if any of the binaries was cross-compiled, and the Git details if
+        // any of the directories is part of a Git worktree. Removed from the back, to keep the
+        // column indices valid.
+        if self.0.iter().all(|row| row.target.is_empty()) {
+            table.with(Disable::column(Columns::single(7)));
+        }
+        if self.0.iter().all(|row| row.commit.is_empty()) {
+            table.with(Disable::column(Columns::new(2..6)));
+        }
+
+        writeln!(
+            f,
+            "{}",
+            table
                 .with(Panel::header(
-                    colors::green(format_args!("Updates from {}", "git"))
+                    colors::green(format_args!("Updates from {}", "local paths"))
                         .bold()
                         .to_string()
                 ))
@@ -195,25 +644,230 @@ impl<'a> Display for GitTable<'a> {
                         .with(Alignment::center())
                         .with(Padding::new(1, 1, 0, 1))
                 )
-                // Draw strait line under the headers
+                // Draw straight line under the headers
+                .with(
+                    Style::blank().horizontals([(2, HorizontalLine::new('─').intersection('─'))])
+                )
+        )
+    }
+}
+
+/// Single row for the [`PathTable`], that can be used with [`tabled`].
+#[derive(Tabled)]
+struct PathRow {
+    #[tabled(rename = "Name")]
+    name: String,
+    #[tabled(rename = "Directory")]
+    directory: String,
+    #[tabled(rename = "Branch")]
+    branch: String,
+    #[tabled(rename = "Commit")]
+    commit: String,
+    #[tabled(rename = "Worktree")]
+    worktree: String,
+    #[tabled(rename = "Since install")]
+    ahead: String,
+    #[tabled(rename = "Changes")]
+    changes: String,
+    #[tabled(rename = "Target")]
+    target: String,
+}
+
+/// Format the time that passed since the given point in time, in the largest fitting unit.
+fn display_age(time: SystemTime) -> String {
+    let secs = time.elapsed().unwrap_or_default().as_secs();
+
+    let (value, unit) = match secs {
+        0..=59 => (secs, "second"),
+        60..=3599 => (secs / 60, "minute"),
+        3600..=86_399 => (secs / 3600, "hour"),
+        _ => (secs / 86_400, "day"),
+    };
+
+    format!("{value} {unit}{} ago", if value == 1 { "" } else { "s" })
+}
+
+/// The summary table lists the outcome of all attempted installations.
+#[derive(Default)]
+pub struct SummaryTable(Vec<SummaryRow>);
+
+impl SummaryTable {
+    pub fn add(&mut self, result: &InstallResult) {
+        self.0.push(SummaryRow {
+            name: result.package.name.clone(),
+            outcome: display_outcome(result.outcome),
+            change: result.change.clone(),
+            time: if result.outcome == InstallOutcome::Skipped {
+                String::new()
+            } else {
+                display_duration(result.duration)
+            },
+            error: result
+                .error
+                .iter()
+                .chain(&result.details)
+                .map(|line| colors::dimmed(line).to_string())
+                .chain(
+                    result
+                        .log
+                        .iter()
+                        .filter(|_| result.outcome == InstallOutcome::Failed)
+                        .map(|log| format!("see {}", colors::dimmed(log.display()))),
+                )
+                .collect::<Vec<_>>()
+                .join("\n"),
+        });
+    }
+}
+
+impl<'a> FromIterator<&'a InstallResult> for SummaryTable {
+    fn from_iter<T: IntoIterator<Item = &'a InstallResult>>(iter: T) -> Self {
+        let mut table = Self::default();
+        for result in iter {
+            table.add(result);
+        }
+
+        table
+    }
+}
+
+impl Display for SummaryTable {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut table = Table::new(&self.0);
+
+        if self.0.iter().all(|row| row.error.is_empty()) {
+            table.with(Disable::column(Columns::single(4)));
+        }
+
+        writeln!(
+            f,
+            "{}",
+            table
+                .with(Panel::header(
+                    colors::green("Installation summary").bold().to_string()
+                ))
+                .with(
+                    Modify::new(Rows::first())
+                        .with(Alignment::center())
+                        .with(Padding::new(1, 1, 0, 1))
+                )
+                // Draw straight line under the headers
                 .with(Style::blank().horizontals([(2, HorizontalLine::new('─').intersection('─'))]))
-                // Draw arrow between old and new commit
-                .with(Modify::new(Segment::new(2.., 2..=2)).with(Border::new().set_right('➞')),)
-                // Add spacing between old and new commit
-                .with(Modify::new(Columns::single(2)).with(Padding::new(1, 2, 0, 0)))
-                .with(Modify::new(Columns::single(3)).with(Padding::new(2, 1, 0, 0)))
-                // Align commit details and reduce padding
+                .with(Modify::new(Segment::new(2.., 3..=3)).with(Alignment::right()))
+        )
+    }
+}
+
+/// Single row for the [`SummaryTable`], that can be used with [`tabled`].
+#[derive(Tabled)]
+#[tabled(rename_all = "PascalCase")]
+struct SummaryRow {
+    name: String,
+    outcome: String,
+    change: String,
+    time: String,
+    error: String,
+}
+
+/// The history table lists past installation attempts, as recorded in the history file.
+#[derive(Default)]
+pub struct HistoryTable(Vec<HistoryRow>);
+
+impl HistoryTable {
+    pub fn add(&mut self, entry: &Entry) {
+        self.0.push(HistoryRow {
+            time: display_age(entry.time()),
+            name: entry.name.clone(),
+            source: entry.source.clone(),
+            change: entry.change.clone(),
+            outcome: display_outcome(entry.outcome),
+            duration: if entry.outcome == InstallOutcome::Skipped {
+                String::new()
+            } else {
+                display_duration(Duration::from_secs_f64(entry.duration))
+            },
+            rustc: entry.rustc.clone(),
+            error: entry
+                .error
+                .as_deref()
+                .map(|e| colors::dimmed(e).to_string())
+                .unwrap_or_default(),
+        });
+    }
+}
+
+impl<'a> FromIterator<&'a Entry> for HistoryTable {
+    fn from_iter<T: IntoIterator<Item = &'a Entry>>(iter: T) -> Self {
+        let mut table = Self::default();
+        for entry in iter {
+            table.add(entry);
+        }
+
+        table
+    }
+}
+
+impl Display for HistoryTable {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut table = Table::new(&self.0);
+
+        if self.0.iter().all(|row| row.error.is_empty()) {
+            table.with(Disable::column(Columns::single(7)));
+        }
+
+        writeln!(
+            f,
+            "{}",
+            table
+                .with(Panel::header(
+                    colors::green("Installation history").bold().to_string()
+                ))
                 .with(
-                    Modify::new(Segment::new(2.., 4..))
-                        .with(Alignment::right())
-                        .with(Padding::zero()),
+                    Modify::new(Rows::first())
+                        .with(Alignment::center())
+                        .with(Padding::new(1, 1, 0, 1))
                 )
-                .with(Modify::new(Columns::single(4)).with(Padding::new(1, 0, 0, 0)))
-                .with(Modify::new(Columns::single(7)).with(Padding::new(0, 1, 0, 0)))
+                // Draw straight line under the headers
+                .with(Style::blank().horizontals([(2, HorizontalLine::new('─').intersection('─'))]))
+                .with(Modify::new(Segment::new(2.., 5..=5)).with(Alignment::right()))
         )
     }
 }
 
+/// Single row for the [`HistoryTable`], that can be used with [`tabled`].
+#[derive(Tabled)]
+#[tabled(rename_all = "PascalCase")]
+struct HistoryRow {
+    time: String,
+    name: String,
+    source: String,
+    change: String,
+    outcome: String,
+    duration: String,
+    rustc: String,
+    error: String,
+}
+
+/// Color the outcome of an installation, with failures standing out the most.
+fn display_outcome(outcome: InstallOutcome) -> String {
+    match outcome {
+        InstallOutcome::Updated => colors::green("updated").to_string(),
+        InstallOutcome::Failed => colors::red("failed").bold().to_string(),
+        InstallOutcome::Skipped => colors::yellow("skipped").to_string(),
+    }
+}
+
+/// Format the duration of a build in minutes and seconds.
+pub(crate) fn display_duration(value: Duration) -> String {
+    let secs = value.as_secs();
+
+    if secs < 60 {
+        format!("{:.1}s", value.as_secs_f64())
+    } else {
+        format!("{}m {:02}s", secs / 60, secs % 60)
+    }
+}
+
 /// Single row for the [`GitTable`], that can be used with [`tabled`].
 #[derive(Tabled)]
 struct GitRow<'a> {
@@ -233,6 +887,24 @@ struct GitRow<'a> {
     insertions: usize,
     #[tabled(rename = "", display_with = "display_deletions")]
     deletions: usize,
+    #[tabled(rename = "Target")]
+    target: &'a str,
+}
+
+impl<'a> GitRow<'a> {
+    fn new(name: &'a str, info: &'a GitInfo, target: Option<&'a str>) -> Self {
+        Self {
+            name,
+            r#type: &info.r#type,
+            old_commit: info.old_commit,
+            new_commit: info.new_commit,
+            commits: info.changes.commits,
+            files_changed: info.changes.files_changed,
+            insertions: info.changes.insertions,
+            deletions: info.changes.deletions,
+            target: target.unwrap_or_default(),
+        }
+    }
 }
 
 fn display_type(value: &str) -> String {